@@ -0,0 +1,25 @@
+#!/bin/sh
+# Records the output of the tools in each given folder for the sample document
+# The files are named after the version banner of the tool, e.g. pdfinfo/xpdf-4.04.txt
+# usage: fixtures/record.sh report.pdf /opt/xpdf-tools-3.04/bin64 /opt/xpdf-tools-4.04/bin64 /usr/bin
+set -eu
+
+pdf=$1
+shift
+here=$(dirname "$0")
+
+for dir in "$@"; do
+    banner=$("$dir/pdfinfo" -v 2>&1)
+    version=$(echo "$banner" | sed -n 's/^pdfinfo version \([0-9.]*\).*/\1/p')
+    case "$banner" in
+        *Poppler*) name="poppler-$version" ;;
+        *) name="xpdf-$version" ;;
+    esac
+
+    "$dir/pdfinfo" "$pdf" > "$here/pdfinfo/$name.txt"
+    "$dir/pdffonts" "$pdf" > "$here/pdffonts/$name.txt"
+    "$dir/pdftotext" "$pdf" - > "$here/pdftotext/$name.txt"
+    # xpdf 3.x has no -list
+    "$dir/pdfimages" -list "$pdf" > "$here/pdfimages/$name.txt" || rm -f "$here/pdfimages/$name.txt"
+    echo "recorded $banner" | head -n 1
+done
@@ -1,4 +0,0 @@
-use xpdf_tools::get_version;
-fn main() {
-    println!("{}", get_version());
-}
\ No newline at end of file
@@ -0,0 +1,500 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use regex::Regex;
+use xpdf_tools::corpus::{self, ReportFormat};
+use xpdf_tools::grep::{GrepMatch, GrepOptions};
+use xpdf_tools::json::Json;
+use xpdf_tools::types::XpdfArgs;
+use xpdf_tools::{get_version, PdfError, PdfErrorKind, XpdfTools};
+
+const USAGE: &str = "Usage: xpdf-tools <command> [options] <file or glob>...
+
+Commands:
+  info          document information (pdfinfo)
+  text          extracted text (pdftotext)
+  fonts         used fonts (pdffonts)
+  images        list images, with --out extract them (pdfimages)
+  attachments   list embedded files, with --out save them (pdfdetach)
+  render        render pages as images into --out (pdftopng / pdftoppm)
+  corpus        metadata report of every pdf below the given folders (pdfinfo)
+  grep <regex>  matching lines as file:page:line:text, like grep -n (pdftotext)
+
+Options:
+  --tools <dir>     folder with the xpdf tools, default: XPDF_TOOLS_DIR, PATH and common prefixes
+  --opw <password>  owner password
+  --upw <password>  user password
+  -f, --first <n>   first page
+  -l, --last <n>    last page
+  --enc <name>      output text encoding
+  --out <dir>       output folder for images, attachments and render
+  --dpi <n>         resolution for render, default 150
+  --json            one JSON object per file and line
+  --csv             corpus report as CSV instead of JSON Lines
+  --report <file>   write the corpus report into the file instead of stdout
+  --text            human readable output (default)
+  -i                grep: ignore case
+  -w                grep: match whole words only
+  -m <n>            grep: stop after n matching lines per file
+  -C <n>            grep: print n lines of context
+  -h, --help        print this help
+  -V, --version     print the version
+
+Exit codes:
+  0 success, 1 a file failed or grep found nothing, 2 usage error, 3 xpdf tools not usable, 4 a file needs a password";
+
+const EXIT_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_TOOLS: u8 = 3;
+const EXIT_PASSWORD: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+struct Options {
+    command: String,
+    files: Vec<PathBuf>,
+    tools_folder: Option<PathBuf>,
+    format: Format,
+    extra_args: Vec<XpdfArgs>,
+    out_dir: Option<PathBuf>,
+    dpi: u32,
+    report_format: ReportFormat,
+    report_file: Option<PathBuf>,
+    pattern: Option<Regex>,
+    grep: GrepOptions,
+}
+
+const COMMANDS: &[&str] = &["info", "text", "fonts", "images", "attachments", "render", "corpus", "grep"];
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        command: String::new(),
+        files: vec![],
+        tools_folder: None,
+        format: Format::Text,
+        extra_args: vec![],
+        out_dir: None,
+        dpi: 150,
+        report_format: ReportFormat::JsonLines,
+        report_file: None,
+        pattern: None,
+        grep: GrepOptions::default(),
+    };
+    let mut patterns = vec![];
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+
+        match arg.as_str() {
+            "--tools" => options.tools_folder = Some(PathBuf::from(value(&arg)?)),
+            "--opw" => options.extra_args.push(XpdfArgs::OwnerPassword(value(&arg)?.into())),
+            "--upw" => options.extra_args.push(XpdfArgs::UserPassword(value(&arg)?.into())),
+            "-f" | "--first" => options.extra_args.push(XpdfArgs::FirstPage(number(&arg, value(&arg)?)?)),
+            "-l" | "--last" => options.extra_args.push(XpdfArgs::LastPage(number(&arg, value(&arg)?)?)),
+            "--enc" => options.extra_args.push(XpdfArgs::Encoding(value(&arg)?)),
+            "--out" => options.out_dir = Some(PathBuf::from(value(&arg)?)),
+            "--dpi" => options.dpi = number(&arg, value(&arg)?)?,
+            "--json" => options.format = Format::Json,
+            "--text" => options.format = Format::Text,
+            "--csv" => options.report_format = ReportFormat::Csv,
+            "--report" => options.report_file = Some(PathBuf::from(value(&arg)?)),
+            "-i" => options.grep.ignore_case = true,
+            "-w" => options.grep.whole_word = true,
+            "-m" => options.grep.max_matches = Some(number(&arg, value(&arg)?)?),
+            "-C" => options.grep.context = number(&arg, value(&arg)?)?,
+            a if a.starts_with('-') && a.len() > 1 => return Err(format!("unknown option {}", a)),
+            _ if options.command.is_empty() => {
+                if !COMMANDS.contains(&arg.as_str()) {
+                    return Err(format!("unknown command {}", arg));
+                }
+                options.command = arg;
+            },
+            _ if options.command == "grep" && options.pattern.is_none() => {
+                options.pattern = Some(Regex::new(&arg).map_err(|e| format!("invalid regex {}: {}", arg, e))?);
+            },
+            _ => patterns.push(arg),
+        }
+    }
+
+    if options.command.is_empty() {
+        return Err("missing command".into());
+    }
+    if options.command == "grep" && options.pattern.is_none() {
+        return Err("grep needs a regex".into());
+    }
+    for pattern in patterns {
+        options.files.extend(expand(&pattern)?);
+    }
+    if options.files.is_empty() {
+        return Err("no pdf files given".into());
+    }
+    if options.command == "corpus" && !options.files.iter().all(|f| f.is_dir()) {
+        return Err("corpus needs folders".into());
+    }
+    Ok(options)
+}
+
+fn number<T: FromStr>(name: &str, text: String) -> Result<T, String> {
+    text.parse().map_err(|_| format!("{} needs a number, got {}", name, text))
+}
+
+//Expands glob patterns, the shell does this on unix but not on windows
+fn expand(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![PathBuf::from(pattern)]);
+    }
+    let paths = glob::glob(pattern).map_err(|e| format!("invalid glob {}: {}", pattern, e))?;
+    let files: Vec<PathBuf> = paths.filter_map(|p| p.ok()).collect();
+    if files.is_empty() {
+        return Err(format!("no files match {}", pattern));
+    }
+    Ok(files)
+}
+
+//Runs the command on one file, returns the text output and the JSON value
+fn run(tools: &XpdfTools, options: &Options, file: &Path) -> Result<(String, Json), PdfError> {
+    let out_dir = options.out_dir.as_deref().unwrap_or(Path::new("."));
+
+    match options.command.as_str() {
+        "info" => {
+            let info = tools.pdf_info(file)?;
+            let text = info.info_map.iter()
+                .filter_map(|(label, value)| value.as_ref().map(|v| format!("{}: {}\n", label, v)))
+                .collect();
+            let json = info.info_map.iter()
+                .map(|(label, value)| (label.clone(), value.as_ref().map(Json::str).unwrap_or(Json::Null)))
+                .collect();
+            Ok((text, Json::object(vec![("info", Json::Object(json))])))
+        },
+        "text" => {
+            let text = tools.pdf_text_pages(file)?;
+            let pages = text.pages.iter().map(Json::str).collect();
+            Ok((text.pages.join("\x0c"), Json::object(vec![
+                ("first_page", Json::Number(text.first_page as u64)),
+                ("pages", Json::Array(pages)),
+            ])))
+        },
+        "fonts" => {
+            let fonts = tools.pdf_fonts(file)?.fonts;
+            let text = fonts.iter()
+                .map(|font| format!("{:<40} {:<18} emb:{} sub:{} uni:{}\n", font.name, font.font_type, yes_no(font.embedded), yes_no(font.subset), yes_no(font.unicode)))
+                .collect();
+            let json = fonts.iter().map(|font| Json::object(vec![
+                ("name", Json::str(&font.name)),
+                ("type", Json::str(&font.font_type)),
+                ("encoding", font.encoding.as_ref().map(Json::str).unwrap_or(Json::Null)),
+                ("embedded", Json::Bool(font.embedded)),
+                ("subset", Json::Bool(font.subset)),
+                ("unicode", Json::Bool(font.unicode)),
+            ])).collect();
+            Ok((text, Json::object(vec![("fonts", Json::Array(json))])))
+        },
+        "images" if options.out_dir.is_some() => {
+            let files = tools.pdf_images_extract(file, out_dir)?;
+            Ok(files_output(&files))
+        },
+        "images" => {
+            let images = tools.pdf_images(file)?.images;
+            let text = images.iter()
+                .map(|image| format!("page {:>4} {:<7} {}x{} {} {}\n", image.page, image.image_type, image.width, image.height, image.color, image.encoding))
+                .collect();
+            let json = images.iter().map(|image| Json::object(vec![
+                ("page", Json::Number(image.page as u64)),
+                ("type", Json::str(&image.image_type)),
+                ("width", Json::Number(image.width.into())),
+                ("height", Json::Number(image.height.into())),
+                ("color", Json::str(&image.color)),
+                ("encoding", Json::str(&image.encoding)),
+            ])).collect();
+            Ok((text, Json::object(vec![("images", Json::Array(json))])))
+        },
+        "attachments" if options.out_dir.is_some() => {
+            let files = tools.pdf_attachments_save(file, out_dir)?;
+            Ok(files_output(&files))
+        },
+        "attachments" => {
+            let attachments = tools.pdf_attachments(file)?;
+            let text = attachments.iter().map(|a| format!("{}: {}\n", a.index, a.name)).collect();
+            let json = attachments.iter().map(|a| Json::object(vec![
+                ("index", Json::Number(a.index as u64)),
+                ("name", Json::str(&a.name)),
+            ])).collect();
+            Ok((text, Json::object(vec![("attachments", Json::Array(json))])))
+        },
+        "render" => {
+            let files = tools.pdf_render(file, out_dir, options.dpi)?;
+            Ok(files_output(&files))
+        },
+        "grep" => {
+            let regex = options.pattern.as_ref().expect("checked in parse_args");
+            let mut results = tools.grep(&[file], regex, options.grep.clone())?;
+            if let Some((_, e)) = results.errors.pop() {
+                return Err(e);
+            }
+            let matches = results.matches;
+            //file names like grep, only with several files
+            let prefix = match options.files.len() > 1 {
+                true => format!("{}:", file.display()),
+                false => String::new(),
+            };
+            let text = grep_lines(&matches, &prefix, options.grep.context > 0);
+            let json = matches.iter().map(|m| Json::object(vec![
+                ("page", Json::Number(m.page as u64)),
+                ("line", Json::Number(m.line as u64)),
+                ("text", Json::str(&m.text)),
+                ("before", Json::Array(m.before.iter().map(Json::str).collect())),
+                ("after", Json::Array(m.after.iter().map(Json::str).collect())),
+            ])).collect();
+            Ok((text, Json::object(vec![("matches", Json::Array(json))])))
+        },
+        _ => unreachable!("commands are checked in parse_args"),
+    }
+}
+
+//The matches and their context like grep -n -C: overlapping context is printed once,
+//"--" separates groups of lines which do not touch
+fn grep_lines(matches: &[GrepMatch], prefix: &str, context: bool) -> String {
+    //(page, line) -> (is a match, text)
+    let mut lines: BTreeMap<(usize, usize), (bool, &str)> = BTreeMap::new();
+    for m in matches {
+        for (n, line) in m.before.iter().enumerate() {
+            lines.entry((m.page, m.line - m.before.len() + n)).or_insert((false, line));
+        }
+        lines.insert((m.page, m.line), (true, &m.text));
+        for (n, line) in m.after.iter().enumerate() {
+            lines.entry((m.page, m.line + 1 + n)).or_insert((false, line));
+        }
+    }
+
+    let context_prefix = prefix.replace(':', "-");
+    let mut text = String::new();
+    let mut previous: Option<(usize, usize)> = None;
+    for ((page, line), (is_match, line_text)) in lines {
+        if context && previous.is_some_and(|previous| previous != (page, line - 1)) {
+            text.push_str("--\n");
+        }
+        match is_match {
+            true => text.push_str(&format!("{}{}:{}:{}\n", prefix, page, line, line_text)),
+            false => text.push_str(&format!("{}{}-{}-{}\n", context_prefix, page, line, line_text)),
+        }
+        previous = Some((page, line));
+    }
+    text
+}
+
+fn files_output(files: &[PathBuf]) -> (String, Json) {
+    let text = files.iter().map(|f| format!("{}\n", f.display())).collect();
+    let json = files.iter().map(|f| Json::path(f)).collect();
+    (text, Json::object(vec![("files", Json::Array(json))]))
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+//Writes one report over all folders, exit code 1 if a file failed
+fn corpus(tools: &XpdfTools, options: &Options) -> io::Result<u8> {
+    let mut out: Box<dyn Write> = match &options.report_file {
+        Some(file) => Box::new(BufWriter::new(File::create(file)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let mut code = 0;
+    for folder in &options.files {
+        let summary = corpus::corpus_report(tools, folder, options.report_format, &mut out)?;
+        if summary.failed > 0 || summary.unreadable_dirs > 0 {
+            code = EXIT_FAILED;
+        }
+    }
+    out.flush()?;
+    Ok(code)
+}
+
+fn exit_code(kind: PdfErrorKind) -> u8 {
+    match kind {
+        PdfErrorKind::PasswordRequired => EXIT_PASSWORD,
+        PdfErrorKind::NoXpdfToolsDirectory | PdfErrorKind::ToolNotAvailable | PdfErrorKind::ToolNotUsable => EXIT_TOOLS,
+        PdfErrorKind::UnsupportedArgument => EXIT_USAGE,
+        _ => EXIT_FAILED,
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    if args.iter().any(|a| a == "-V" || a == "--version") {
+        println!("{}", get_version());
+        return ExitCode::SUCCESS;
+    }
+
+    let options = match parse_args(args.into_iter()) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("xpdf-tools: {}\n\n{}", message, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        },
+    };
+
+    let builder = match &options.tools_folder {
+        Some(folder) => XpdfTools::builder(folder.clone()),
+        None => XpdfTools::discover(),
+    };
+    let tools = match builder.and_then(|builder| builder.extra_args(options.extra_args.clone()).build()) {
+        Ok(tools) => tools,
+        Err(e) => {
+            eprintln!("xpdf-tools: {}", e);
+            return ExitCode::from(EXIT_TOOLS);
+        },
+    };
+
+    if options.command == "corpus" {
+        return match corpus(&tools, &options) {
+            Ok(code) => ExitCode::from(code),
+            Err(e) => {
+                eprintln!("xpdf-tools: {}", e);
+                ExitCode::from(EXIT_FAILED)
+            },
+        };
+    }
+
+    let mut code = 0;
+    let grep = options.command == "grep";
+    //grep prefixes every line with the file name itself
+    let show_names = options.files.len() > 1 && !grep;
+    let mut found = false;
+    for file in &options.files {
+        match run(&tools, &options, file) {
+            Ok((text, json)) => {
+                match options.format {
+                    Format::Text if grep && found && options.grep.context > 0 && !text.is_empty() => print!("--\n{}", text),
+                    Format::Text if show_names => print!("==> {} <==\n{}\n", file.display(), text),
+                    Format::Text => print!("{}", text),
+                    Format::Json => {
+                        let Json::Object(mut fields) = json else { unreachable!() };
+                        fields.insert(0, ("file".into(), Json::path(file)));
+                        println!("{}", Json::Object(fields));
+                    },
+                }
+                found |= !text.is_empty();
+            },
+            Err(e) => {
+                eprintln!("xpdf-tools: {}: {}", file.display(), e);
+                if let Some(command) = e.command() {
+                    eprintln!("xpdf-tools: command: {}", command);
+                    if !command.stderr.trim().is_empty() {
+                        eprintln!("{}", command.stderr.trim_end());
+                    }
+                }
+                if options.format == Format::Json {
+                    println!("{}", Json::object(vec![
+                        ("file", Json::path(file)),
+                        ("error", Json::str(format!("{:?}", e.error_kind))),
+                        ("message", Json::str(e.to_string())),
+                    ]));
+                }
+                code = code.max(exit_code(e.error_kind));
+            },
+        }
+    }
+    if grep && !found {
+        code = code.max(EXIT_FAILED);
+    }
+    ExitCode::from(code)
+}
+
+#[cfg(test)]
+fn parse(args: &[&str]) -> Result<Options, String> {
+    parse_args(args.iter().map(|arg| arg.to_string()))
+}
+
+#[test]
+fn test_parse_args() {
+    let options = parse(&["text", "-f", "2", "--last", "3", "--opw", "secret", "--dpi", "300", "--json", "a.pdf", "b.pdf"]).unwrap();
+    assert_eq!(options.command, "text");
+    assert_eq!(options.files, vec![PathBuf::from("a.pdf"), PathBuf::from("b.pdf")]);
+    assert_eq!(options.extra_args, vec![XpdfArgs::FirstPage(2), XpdfArgs::LastPage(3), XpdfArgs::OwnerPassword("secret".into())]);
+    assert_eq!((options.dpi, options.format), (300, Format::Json));
+
+    let options = parse(&["grep", "-i", "-C", "2", "-m", "5", "rev.nue", "a.pdf"]).unwrap();
+    assert_eq!(options.pattern.unwrap().as_str(), "rev.nue");
+    assert_eq!((options.grep.ignore_case, options.grep.context, options.grep.max_matches), (true, 2, Some(5)));
+
+    assert_eq!(parse(&["a.pdf"]).unwrap_err(), "unknown command a.pdf");
+    assert_eq!(parse(&["info", "--bogus", "a.pdf"]).unwrap_err(), "unknown option --bogus");
+    assert_eq!(parse(&["info", "-f"]).unwrap_err(), "-f needs a value");
+    assert_eq!(parse(&["info", "-f", "two", "a.pdf"]).unwrap_err(), "-f needs a number, got two");
+    //too large for u32 instead of silently truncated
+    assert_eq!(parse(&["render", "--dpi", "4294967446", "a.pdf"]).unwrap_err(), "--dpi needs a number, got 4294967446");
+    assert_eq!(parse(&["--json"]).unwrap_err(), "missing command");
+    assert_eq!(parse(&["grep"]).unwrap_err(), "grep needs a regex");
+    assert_eq!(parse(&["info"]).unwrap_err(), "no pdf files given");
+    assert_eq!(parse(&["corpus", "a.pdf"]).unwrap_err(), "corpus needs folders");
+}
+
+#[test]
+fn test_expand() {
+    let dir = env::temp_dir().join(format!("xpdf_cli_expand_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in ["b.pdf", "a.pdf", "notes.txt"] {
+        std::fs::write(dir.join(name), "").unwrap();
+    }
+    let pattern = dir.join("*.pdf").to_string_lossy().into_owned();
+    let missing = dir.join("*.png").to_string_lossy().into_owned();
+    let (files, none) = (expand(&pattern), expand(&missing));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(files.unwrap(), vec![dir.join("a.pdf"), dir.join("b.pdf")]);
+    assert_eq!(none.unwrap_err(), format!("no files match {}", missing));
+    //plain paths are passed on as they are, a missing file fails later with the tool error
+    assert_eq!(expand("missing.pdf").unwrap(), vec![PathBuf::from("missing.pdf")]);
+}
+
+#[test]
+fn test_exit_code() {
+    assert_eq!(exit_code(PdfErrorKind::PasswordRequired), EXIT_PASSWORD);
+    assert_eq!(exit_code(PdfErrorKind::ToolNotAvailable), EXIT_TOOLS);
+    assert_eq!(exit_code(PdfErrorKind::ToolNotUsable), EXIT_TOOLS);
+    assert_eq!(exit_code(PdfErrorKind::UnsupportedArgument), EXIT_USAGE);
+    assert_eq!(exit_code(PdfErrorKind::PdfInfoError), EXIT_FAILED);
+}
+
+#[test]
+fn test_grep_lines() {
+    use xpdf_tools::grep::grep_text;
+    use xpdf_tools::xpdf_text::PdfText;
+
+    let text = PdfText { first_page: 1, pages: vec![
+        "one\ntwo fee\nthree\nfour fee\nfive\nsix\nseven\neight fee\n".into(),
+        "fee on page two\n".into(),
+    ]};
+    let options = GrepOptions { context: 1, ..Default::default() };
+    let matches = grep_text(Path::new("a.pdf"), &text, &Regex::new("fee").unwrap(), &options);
+
+    //the two matches one line apart share their context, "--" only between separate groups
+    assert_eq!(grep_lines(&matches, "", true), "\
+1-1-one
+1:2:two fee
+1-3-three
+1:4:four fee
+1-5-five
+--
+1-7-seven
+1:8:eight fee
+--
+2:1:fee on page two
+");
+    let matches = grep_text(Path::new("a.pdf"), &text, &Regex::new("fee").unwrap(), &GrepOptions::default());
+    assert_eq!(grep_lines(&matches[..2], "a.pdf:", false), "a.pdf:1:2:two fee\na.pdf:1:4:four fee\n");
+}
@@ -0,0 +1,233 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+use crate::version::ToolVersion;
+
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// On-disk cache of tool outputs
+/// An entry is keyed by the hash of the pdf file contents and the hash of the tool,
+/// its normalized arguments and version, so renamed or moved files still hit the cache.
+/// Entries are files named `<content hash>-<call hash>`, the least recently used ones
+/// are evicted when the cache grows over its size limit. Other files in the folder are left alone
+#[derive(Debug, Clone)]
+pub struct ResultCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    //approximate size, counted up on put, the folder is only scanned when it exceeds the limit
+    size: Arc<AtomicU64>,
+}
+
+impl ResultCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let mut cache = ResultCache { dir, max_bytes, size: Arc::default() };
+        cache.size = Arc::new(AtomicU64::new(cache.size()?));
+        Ok(cache)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Hex encoded sha256 of the file contents
+    pub fn content_hash(pdf_file: &Path) -> io::Result<String> {
+        let mut file = File::open(pdf_file)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hex(&hasher.finalize()))
+    }
+
+    /// The key of a tool call, the pdf file argument must already be replaced by a placeholder
+    pub fn key(content_hash: &str, tool: &str, normalized_args: &[String], version: Option<&ToolVersion>) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(tool.as_bytes());
+        for arg in normalized_args {
+            //separator which can not be part of an argument
+            hasher.update([0]);
+            hasher.update(arg.as_bytes());
+        }
+        hasher.update([0]);
+        hasher.update(version.map(|v| v.to_string()).unwrap_or_default().as_bytes());
+        format!("{}-{}", content_hash, hex(&hasher.finalize()))
+    }
+
+    /// The cached output, a hit marks the entry as recently used
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let path = self.dir.join(key);
+        let data = fs::read(&path).ok()?;
+        if let Ok(file) = File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(data)
+    }
+
+    /// Stores the output, written to a temporary file and renamed so readers never see partial entries
+    pub fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        if !is_key(key) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Not a cache key: {:?}", key)));
+        }
+        let len = data.len() as u64;
+        if len > self.max_bytes {
+            return Ok(());
+        }
+        //unique per call, threads may write the same key at once
+        let tmp = self.dir.join(format!(".{}.{}.{}.tmp", key, std::process::id(), TEMP_FILES.fetch_add(1, Ordering::Relaxed)));
+        if let Err(e) = fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, self.dir.join(key))) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        if self.size.fetch_add(len, Ordering::Relaxed) + len > self.max_bytes {
+            self.evict()?;
+        }
+        Ok(())
+    }
+
+    /// Removes all entries of the file, returns the number of removed entries
+    pub fn invalidate(&self, pdf_file: &Path) -> io::Result<usize> {
+        let prefix = format!("{}-", Self::content_hash(pdf_file)?);
+        let mut removed = 0;
+        for (path, size, _) in self.entries()? {
+            if path.file_name().is_some_and(|n| n.to_string_lossy().starts_with(&prefix)) {
+                fs::remove_file(path)?;
+                let _ = self.size.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| Some(total.saturating_sub(size)));
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        for (path, _, _) in self.entries()? {
+            fs::remove_file(path)?;
+        }
+        self.size.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Total size of all entries in bytes
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(self.entries()?.iter().map(|(_, size, _)| size).sum())
+    }
+
+    //Removes the least recently used entries until the cache fits its size limit
+    //The scan also corrects the approximate size, e.g. for overwritten keys or entries of other processes
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        entries.sort_by_key(|(_, _, used)| *used);
+        for (path, size, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            //an entry removed meanwhile by another thread or process is gone all the same
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => total -= size,
+            }
+        }
+        self.size.store(total, Ordering::Relaxed);
+        Ok(())
+    }
+
+    //Path, size and last use of every entry, temporary and foreign files are skipped
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut entries = vec![];
+        for entry in self.dir.read_dir()? {
+            let entry = entry?;
+            if !entry.file_name().to_str().is_some_and(is_key) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                entries.push((entry.path(), metadata.len(), metadata.modified()?));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+//Names written by the cache: <sha256 hex>-<sha256 hex>
+fn is_key(name: &str) -> bool {
+    let sha256 = |part: &str| part.len() == 64 && part.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    name.split_once('-').is_some_and(|(content, call)| sha256(content) && sha256(call))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_result_cache() {
+    use std::time::Duration;
+
+    let root = std::env::temp_dir().join(format!("xpdf_cache_{}", std::process::id()));
+    let cache = ResultCache::new(root.join("cache"), 10).unwrap();
+    let pdf = root.join("a.pdf");
+    fs::write(&pdf, "%PDF-1.7").unwrap();
+
+    let hash = ResultCache::content_hash(&pdf).unwrap();
+    let key = ResultCache::key(&hash, "pdfinfo", &["-meta".into(), "<pdf>".into()], None);
+    assert_ne!(key, ResultCache::key(&hash, "pdfinfo", &["<pdf>".into()], None));
+
+    cache.put(&key, b"Pages: 1").unwrap();
+    assert_eq!(cache.get(&key), Some(b"Pages: 1".to_vec()));
+
+    //the older entry is evicted when the limit of 10 bytes is exceeded
+    let other = ResultCache::key(&hash, "pdftotext", &["<pdf>".into()], None);
+    let old = SystemTime::now() - Duration::from_secs(60);
+    File::options().write(true).open(cache.dir().join(&key)).unwrap().set_modified(old).unwrap();
+    cache.put(&other, b"text").unwrap();
+    assert_eq!(cache.get(&key), None);
+    assert_eq!(cache.get(&other), Some(b"text".to_vec()));
+
+    assert_eq!(cache.invalidate(&pdf).unwrap(), 1);
+    assert_eq!(cache.size().unwrap(), 0);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_result_cache_lazy_eviction() {
+    let root = std::env::temp_dir().join(format!("xpdf_cache_lazy_{}", std::process::id()));
+    let cache = ResultCache::new(root.clone(), 10).unwrap();
+    let key = |tool: &str| ResultCache::key(&"0".repeat(64), tool, &[], None);
+
+    //an entry written by another process is only noticed by the next scan
+    cache.put(&key("pdfinfo"), b"1234").unwrap();
+    fs::write(root.join(key("pdffonts")), b"0123456789").unwrap();
+    //files the cache did not write are neither counted nor removed
+    fs::write(root.join("notes.txt"), b"0123456789").unwrap();
+    fs::write(root.join("other"), b"0123456789").unwrap();
+    cache.put(&key("pdftotext"), b"1234").unwrap();
+    assert_eq!(cache.size().unwrap(), 18);
+    cache.put(&key("pdfimages"), b"1234").unwrap();
+    assert!(cache.size().unwrap() <= 10);
+    assert!(cache.put("../escape", b"1234").is_err());
+
+    //threads writing the same key do not share a temporary file
+    let threads: Vec<_> = (0..8).map(|_| {
+        let (cache, same) = (cache.clone(), key("same"));
+        std::thread::spawn(move || (0..20).all(|_| cache.put(&same, b"data").is_ok()))
+    }).collect();
+    assert!(threads.into_iter().all(|thread| thread.join().unwrap()));
+    assert_eq!(cache.get(&key("same")), Some(b"data".to_vec()));
+
+    cache.clear().unwrap();
+    assert_eq!(cache.size().unwrap(), 0);
+    let foreign = (root.join("notes.txt").exists(), root.join("other").exists());
+    fs::remove_dir_all(&root).unwrap();
+    assert_eq!(foreign, (true, true));
+}
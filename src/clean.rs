@@ -0,0 +1,262 @@
+use std::collections::{HashMap, HashSet};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::xpdf_text::PdfText;
+
+const LIGATURES: &[(char, &str)] = &[
+    ('\u{fb00}', "ff"),
+    ('\u{fb01}', "fi"),
+    ('\u{fb02}', "fl"),
+    ('\u{fb03}', "ffi"),
+    ('\u{fb04}', "ffl"),
+    ('\u{fb05}', "st"),
+    ('\u{fb06}', "st"),
+];
+
+//hyphen-minus, soft hyphen and hyphen
+const HYPHENS: &[char] = &['-', SOFT_HYPHEN, '\u{2010}'];
+//only marks where a word may be split, never part of the word
+const SOFT_HYPHEN: char = '\u{ad}';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+/// Post processing of page-aware pdftotext output
+/// The steps run in this order: ligature expansion, Unicode normalization,
+/// header and footer removal and dehyphenation. All steps are on by default with NFC.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextCleaner {
+    expand_ligatures: bool,
+    normalization: Option<Normalization>,
+    remove_headers_footers: bool,
+    header_footer_lines: usize,
+    min_repeat_ratio: f32,
+    dehyphenate: bool,
+    known_words: HashSet<String>,
+}
+
+impl Default for TextCleaner {
+    fn default() -> Self {
+        TextCleaner {
+            expand_ligatures: true,
+            normalization: Some(Normalization::Nfc),
+            remove_headers_footers: true,
+            header_footer_lines: 2,
+            min_repeat_ratio: 0.5,
+            dehyphenate: true,
+            known_words: HashSet::new(),
+        }
+    }
+}
+
+impl TextCleaner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn expand_ligatures(mut self, expand: bool) -> Self {
+        self.expand_ligatures = expand;
+        self
+    }
+
+    pub fn normalization(mut self, normalization: Option<Normalization>) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Removes lines at the top and bottom of the pages which repeat on other pages
+    /// Digits are ignored when comparing, so "Page 3 of 12" repeats "Page 4 of 12"
+    pub fn remove_headers_footers(mut self, remove: bool) -> Self {
+        self.remove_headers_footers = remove;
+        self
+    }
+
+    /// How many of the first and last lines of a page can be headers or footers, default 2
+    pub fn header_footer_lines(mut self, lines: usize) -> Self {
+        self.header_footer_lines = lines;
+        self
+    }
+
+    /// Share of the pages a line has to repeat on to be removed, default 0.5
+    pub fn min_repeat_ratio(mut self, ratio: f32) -> Self {
+        self.min_repeat_ratio = ratio;
+        self
+    }
+
+    /// Rejoins words split with a hyphen at the end of a line, if the next line starts lower case
+    /// The hyphen is kept, as in "self-service", unless it is a soft hyphen or the joined word is known:
+    /// it occurs unhyphenated elsewhere in the text or is one of the known words
+    pub fn dehyphenate(mut self, dehyphenate: bool) -> Self {
+        self.dehyphenate = dehyphenate;
+        self
+    }
+
+    /// Words whose hyphen is dropped when they are split at a line end, e.g. from a dictionary
+    pub fn known_words<S: AsRef<str>>(mut self, words: impl IntoIterator<Item = S>) -> Self {
+        self.known_words.extend(words.into_iter().map(|word| word.as_ref().to_lowercase()));
+        self
+    }
+
+    pub fn clean(&self, text: &PdfText) -> PdfText {
+        let mut pages: Vec<String> = text.pages.iter().map(|page| self.clean_chars(page)).collect();
+        if self.remove_headers_footers {
+            pages = self.strip_headers_footers(&pages);
+        }
+        if self.dehyphenate {
+            let mut known = self.known_words.clone();
+            known.extend(pages.iter().flat_map(|page| words(page)));
+            pages = pages.iter().map(|page| dehyphenate(page, &known)).collect();
+        }
+        PdfText { first_page: text.first_page, pages }
+    }
+
+    fn clean_chars(&self, page: &str) -> String {
+        let page = match self.expand_ligatures {
+            true => expand_ligatures(page),
+            false => page.to_string(),
+        };
+        match self.normalization {
+            Some(Normalization::Nfc) => page.nfc().collect(),
+            Some(Normalization::Nfd) => page.nfd().collect(),
+            Some(Normalization::Nfkc) => page.nfkc().collect(),
+            Some(Normalization::Nfkd) => page.nfkd().collect(),
+            None => page,
+        }
+    }
+
+    fn strip_headers_footers(&self, pages: &[String]) -> Vec<String> {
+        //a single page has nothing to repeat on
+        if pages.len() < 2 {
+            return pages.to_vec();
+        }
+        let lines: Vec<Vec<&str>> = pages.iter().map(|page| page.lines().collect()).collect();
+
+        //(from top, index of the non blank line) -> signature -> pages
+        let mut counts: HashMap<(bool, usize, String), usize> = HashMap::new();
+        for page in &lines {
+            for (slot, line) in self.edge_lines(page) {
+                *counts.entry((slot.0, slot.1, signature(page[line]))).or_default() += 1;
+            }
+        }
+        let min_pages = ((pages.len() as f32 * self.min_repeat_ratio).ceil() as usize).max(2);
+
+        lines.iter().zip(pages).map(|(page, original)| {
+            let remove: Vec<usize> = self.edge_lines(page)
+                .filter(|(slot, line)| counts[&(slot.0, slot.1, signature(page[*line]))] >= min_pages)
+                .map(|(_, line)| line)
+                .collect();
+            if remove.is_empty() {
+                return original.clone();
+            }
+            let mut kept: String = page.iter().enumerate()
+                .filter(|(i, _)| !remove.contains(i))
+                .map(|(_, line)| format!("{}\n", line))
+                .collect();
+            if !original.ends_with('\n') {
+                kept.pop();
+            }
+            kept
+        }).collect()
+    }
+
+    //((from top, position), line index) of the first and last non blank lines
+    fn edge_lines<'a>(&self, page: &'a [&str]) -> impl Iterator<Item = ((bool, usize), usize)> + 'a {
+        let content: Vec<usize> = (0..page.len()).filter(|i| !page[*i].trim().is_empty()).collect();
+        let n = self.header_footer_lines.min(content.len() / 2);
+        let top: Vec<_> = content.iter().take(n).enumerate().map(|(pos, line)| ((true, pos), *line)).collect();
+        let bottom: Vec<_> = content.iter().rev().take(n).enumerate().map(|(pos, line)| ((false, pos), *line)).collect();
+        top.into_iter().chain(bottom)
+    }
+}
+
+pub fn expand_ligatures(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match LIGATURES.iter().find(|(ligature, _)| *ligature == c) {
+            Some((_, expanded)) => out.push_str(expanded),
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// Joins "infor-" at a line end with "mation" at the start of the next line
+/// The hyphen is dropped for a soft hyphen or if the joined word, lower cased, is known,
+/// otherwise it is kept and "self-" and "service" become "self-service".
+/// The rest of the next line stays on its own line
+pub fn dehyphenate(page: &str, known_words: &HashSet<String>) -> String {
+    let mut lines: Vec<String> = page.split('\n').map(|line| line.to_string()).collect();
+    let mut i = 0;
+    while i + 1 < lines.len() {
+        let line = lines[i].trim_end();
+        let split = line.strip_suffix(HYPHENS).filter(|rest| rest.chars().last().is_some_and(char::is_alphabetic));
+        let next = lines[i + 1].trim_start();
+        match (split, next.chars().next()) {
+            (Some(rest), Some(first)) if first.is_lowercase() => {
+                let end = next.find(char::is_whitespace).unwrap_or(next.len());
+                let word = format!("{}{}", rest, &next[..end]);
+                let joined = match line.ends_with(SOFT_HYPHEN) || known_words.contains(&last_word(&word).to_lowercase()) {
+                    true => word,
+                    false => format!("{}-{}", rest, &next[..end]),
+                };
+                let remaining = next[end..].trim_start().to_string();
+                lines[i] = joined;
+                if remaining.is_empty() {
+                    lines.remove(i + 1);
+                } else {
+                    lines[i + 1] = remaining;
+                    i += 1;
+                }
+            },
+            _ => i += 1,
+        }
+    }
+    lines.join("\n")
+}
+
+//The lower cased words of the text
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphabetic()).filter(|word| !word.is_empty()).map(str::to_lowercase)
+}
+
+//The word at the end, "financial infor" + "mation" -> "information"
+fn last_word(text: &str) -> &str {
+    text.rsplit(|c: char| !c.is_alphabetic()).next().unwrap_or(text)
+}
+
+//Header and footer lines are compared without digits and surrounding space
+fn signature(line: &str) -> String {
+    line.trim().chars().map(|c| if c.is_ascii_digit() { '#' } else { c }).collect()
+}
+
+#[test]
+fn test_text_cleaner() {
+    let page = |n: usize, body: &str| format!("ACME Annual Report\n\n{}\n\nPage {} of 3\n", body, n);
+    let text = PdfText { first_page: 1, pages: vec![
+        page(1, "The \u{fb01}nancial infor-\nmation is audited."),
+        page(2, "Cafe\u{301} revenue grew, see the information below."),
+        page(3, "Well-\nKnown brands and self-\n  service stores."),
+    ]};
+
+    let cleaned = TextCleaner::new().clean(&text);
+    assert_eq!(cleaned.pages[0], "\nThe financial information\nis audited.\n\n");
+    assert_eq!(cleaned.pages[1], "\nCaf\u{e9} revenue grew, see the information below.\n\n");
+    assert_eq!(cleaned.pages[2], "\nWell-\nKnown brands and self-service\nstores.\n\n");
+
+    let raw = TextCleaner::new().remove_headers_footers(false).dehyphenate(false).normalization(None).clean(&text);
+    assert_eq!(raw.pages[0], page(1, "The financial infor-\nmation is audited."));
+    assert_eq!(raw.pages[1], page(2, "Cafe\u{301} revenue grew, see the information below."));
+
+    //soft hyphens are always dropped, hard ones only for known words
+    let none = HashSet::new();
+    assert_eq!(dehyphenate("a co-\noperative", &none), "a co-operative");
+    assert_eq!(dehyphenate("a co\u{ad}\noperative", &none), "a cooperative");
+    let known = TextCleaner::new().known_words(["Cooperative"]);
+    assert_eq!(dehyphenate("a co-\noperative", &known.known_words), "a cooperative");
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CONFIG_FILES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextEol {
+    Unix,
+    Dos,
+    Mac,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaperSize {
+    Letter,
+    Legal,
+    A4,
+    A3,
+    /// the size of the pdf page
+    Match,
+    /// width and height in points
+    Custom(u32, u32),
+}
+
+/// Settings written as xpdfrc file, see `man xpdfrc` for the directives
+/// Directives are written in the order they were set, for repeatable ones like fontFile every call adds a line
+/// xpdfrc has no escapes, a value with a line break or a double quote makes `write` and `write_temp` fail
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XpdfConfig {
+    directives: Vec<(String, String)>,
+    //the first directive given an invalid name or value
+    invalid: Option<String>,
+}
+
+impl XpdfConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text_encoding(self, encoding: &str) -> Self {
+        self.checked("textEncoding", &[encoding]).set("textEncoding", quote(encoding))
+    }
+
+    pub fn text_eol(self, eol: TextEol) -> Self {
+        let eol = match eol {
+            TextEol::Unix => "unix",
+            TextEol::Dos => "dos",
+            TextEol::Mac => "mac",
+        };
+        self.set("textEOL", eol.into())
+    }
+
+    pub fn text_page_breaks(self, page_breaks: bool) -> Self {
+        self.set("textPageBreaks", yes_no(page_breaks))
+    }
+
+    pub fn text_keep_tiny_chars(self, keep: bool) -> Self {
+        self.set("textKeepTinyChars", yes_no(keep))
+    }
+
+    /// The font file used for a base 14 or unembedded font
+    pub fn font_file(self, font_name: &str, file: &Path) -> Self {
+        self.checked("fontFile", &[font_name, &file.to_string_lossy()]).add("fontFile", format!("{} {}", quote(font_name), quote_path(file)))
+    }
+
+    /// A folder searched for unembedded fonts
+    pub fn font_dir(self, dir: &Path) -> Self {
+        self.checked("fontDir", &[&dir.to_string_lossy()]).add("fontDir", quote_path(dir))
+    }
+
+    pub fn ps_paper_size(self, size: PaperSize) -> Self {
+        let size = match size {
+            PaperSize::Letter => "letter".into(),
+            PaperSize::Legal => "legal".into(),
+            PaperSize::A4 => "A4".into(),
+            PaperSize::A3 => "A3".into(),
+            PaperSize::Match => "match".into(),
+            PaperSize::Custom(width, height) => format!("{} {}", width, height),
+        };
+        self.set("psPaperSize", size)
+    }
+
+    pub fn antialias(self, antialias: bool) -> Self {
+        self.set("antialias", yes_no(antialias))
+    }
+
+    pub fn vector_antialias(self, antialias: bool) -> Self {
+        self.set("vectorAntialias", yes_no(antialias))
+    }
+
+    /// Maps glyph names like "c65" or "g42" to unicode
+    pub fn map_numeric_char_names(self, map: bool) -> Self {
+        self.set("mapNumericCharNames", yes_no(map))
+    }
+
+    pub fn map_unknown_char_names(self, map: bool) -> Self {
+        self.set("mapUnknownCharNames", yes_no(map))
+    }
+
+    /// Reads another xpdfrc file, e.g. the system wide one
+    pub fn include(self, file: &Path) -> Self {
+        self.checked("include", &[&file.to_string_lossy()]).add("include", quote_path(file))
+    }
+
+    /// Any other directive, the value is written as given
+    pub fn directive(self, name: &str, value: &str) -> Self {
+        self.checked(name, &[value]).add(name, value.into())
+    }
+
+    //A line break or a double quote would end the value and could start another directive
+    fn checked(mut self, name: &str, values: &[&str]) -> Self {
+        let invalid = name.is_empty()
+            || name.contains(|c: char| c.is_whitespace() || c == '"')
+            || values.iter().any(|value| value.contains(['\n', '\r', '"']));
+        if invalid && self.invalid.is_none() {
+            self.invalid = Some(name.into());
+        }
+        self
+    }
+
+    fn check(&self) -> io::Result<()> {
+        match &self.invalid {
+            Some(name) => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The directive {:?} has a line break or a double quote in its name or value", name))),
+            None => Ok(()),
+        }
+    }
+
+    //Replaces an earlier value of the directive
+    fn set(mut self, name: &str, value: String) -> Self {
+        self.directives.retain(|(n, _)| n != name);
+        self.add(name, value)
+    }
+
+    fn add(mut self, name: &str, value: String) -> Self {
+        self.directives.push((name.into(), value));
+        self
+    }
+
+    pub fn write(&self, file: &Path) -> io::Result<()> {
+        self.check()?;
+        fs::write(file, self.to_string())
+    }
+
+    /// Writes the config to a new file in the temp folder, removed when the returned value is dropped
+    pub fn write_temp(&self) -> io::Result<ConfigFile> {
+        self.check()?;
+        let path = std::env::temp_dir().join(format!("xpdf-tools-{}-{}.xpdfrc", std::process::id(), CONFIG_FILES.fetch_add(1, Ordering::Relaxed)));
+        let contents = self.to_string();
+        let mut file = File::options().write(true).create_new(true).open(&path)?;
+        file.write_all(contents.as_bytes())?;
+        Ok(ConfigFile { path, contents })
+    }
+}
+
+impl fmt::Display for XpdfConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.directives {
+            writeln!(f, "{} {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A written config file, deleted on drop
+#[derive(Debug)]
+pub struct ConfigFile {
+    path: PathBuf,
+    contents: String,
+}
+
+impl ConfigFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+}
+
+impl Drop for ConfigFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes".into() } else { "no".into() }
+}
+
+//xpdfrc values with spaces are double quoted
+fn quote(value: &str) -> String {
+    match value.contains(char::is_whitespace) {
+        true => format!("\"{}\"", value),
+        false => value.into(),
+    }
+}
+
+fn quote_path(path: &Path) -> String {
+    quote(&path.to_string_lossy())
+}
+
+#[test]
+fn test_xpdf_config() {
+    let config = XpdfConfig::new()
+        .text_encoding("Latin1")
+        .text_eol(TextEol::Dos)
+        .text_page_breaks(false)
+        .font_file("Times-Roman", Path::new("/usr/share/fonts/Times Roman.pfa"))
+        .font_file("Courier", Path::new("/usr/share/fonts/cour.pfa"))
+        .ps_paper_size(PaperSize::Custom(595, 842))
+        .map_numeric_char_names(true)
+        .text_encoding("UTF-8");
+
+    assert_eq!(config.to_string(), "\
+textEOL dos
+textPageBreaks no
+fontFile Times-Roman \"/usr/share/fonts/Times Roman.pfa\"
+fontFile Courier /usr/share/fonts/cour.pfa
+psPaperSize 595 842
+mapNumericCharNames yes
+textEncoding UTF-8
+");
+
+    let file = config.write_temp().unwrap();
+    let path = file.path().to_path_buf();
+    assert_eq!(fs::read_to_string(&path).unwrap(), config.to_string());
+    drop(file);
+    assert!(!path.exists());
+
+    let injected = [
+        XpdfConfig::new().directive("textEncoding", "UTF-8\ninclude /tmp/evil"),
+        XpdfConfig::new().text_encoding("UTF-8\" \"x"),
+        XpdfConfig::new().font_dir(Path::new("/fonts\r\nfontDir /tmp")),
+        XpdfConfig::new().directive("textEOL unix\nfontDir", "/tmp"),
+    ];
+    for config in injected {
+        assert_eq!(config.write_temp().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::csv;
+use crate::json::Json;
+use crate::xpdf_info::PdfInfo;
+use crate::XpdfTools;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    JsonLines,
+    Csv,
+}
+
+/// Metadata of one pdf file of the corpus, error is set if pdfinfo failed
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CorpusRow {
+    pub path: PathBuf,
+    pub pages: Option<usize>,
+    pub pdf_version: Option<String>,
+    pub producer: Option<String>,
+    pub creator: Option<String>,
+    pub encrypted: Option<bool>,
+    pub tagged: Option<bool>,
+    pub form: Option<String>,
+    pub page_size: Option<String>,
+    pub file_size: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl CorpusRow {
+    pub fn from_info(path: &Path, info: &PdfInfo) -> Self {
+        let value = |label: &str| info.info_map.get(label).cloned().flatten();
+        let yes = |label: &str| value(label).map(|v| v.starts_with("yes"));
+
+        CorpusRow {
+            path: path.into(),
+            pages: value("Pages").and_then(|v| v.parse().ok()),
+            pdf_version: value("PDF version"),
+            producer: value("Producer"),
+            creator: value("Creator"),
+            encrypted: yes("Encrypted"),
+            tagged: yes("Tagged"),
+            form: value("Form"),
+            page_size: value("Page size"),
+            //"18436 bytes"
+            file_size: value("File size").and_then(|v| v.split_whitespace().next().and_then(|n| n.parse().ok())),
+            error: None,
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        let text = |v: &Option<String>| v.as_ref().map(Json::str).unwrap_or(Json::Null);
+        let flag = |v: Option<bool>| v.map(Json::Bool).unwrap_or(Json::Null);
+        let number = |v: Option<u64>| v.map(Json::Number).unwrap_or(Json::Null);
+
+        Json::object(vec![
+            ("path", Json::path(&self.path)),
+            ("pages", number(self.pages.map(|p| p as u64))),
+            ("pdf_version", text(&self.pdf_version)),
+            ("producer", text(&self.producer)),
+            ("creator", text(&self.creator)),
+            ("encrypted", flag(self.encrypted)),
+            ("tagged", flag(self.tagged)),
+            ("form", text(&self.form)),
+            ("page_size", text(&self.page_size)),
+            ("file_size", number(self.file_size)),
+            ("error", text(&self.error)),
+        ])
+    }
+
+    fn to_csv(&self) -> String {
+        let text = |v: &Option<String>| v.clone().unwrap_or_default();
+        let flag = |v: Option<bool>| v.map(|b| b.to_string()).unwrap_or_default();
+        csv::row(&[
+            self.path.to_string_lossy().into_owned(),
+            self.pages.map(|p| p.to_string()).unwrap_or_default(),
+            text(&self.pdf_version),
+            text(&self.producer),
+            text(&self.creator),
+            flag(self.encrypted),
+            flag(self.tagged),
+            text(&self.form),
+            text(&self.page_size),
+            self.file_size.map(|s| s.to_string()).unwrap_or_default(),
+            text(&self.error),
+        ])
+    }
+}
+
+const CSV_HEADER: &str = "path,pages,pdf_version,producer,creator,encrypted,tagged,form,page_size,file_size,error";
+
+/// Counts over the whole corpus
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CorpusSummary {
+    pub files: usize,
+    pub failed: usize,
+    /// subfolders skipped because they could not be read
+    pub unreadable_dirs: usize,
+    pub by_producer: BTreeMap<String, usize>,
+    pub by_pdf_version: BTreeMap<String, usize>,
+}
+
+impl CorpusSummary {
+    pub fn add(&mut self, row: &CorpusRow) {
+        self.files += 1;
+        if row.error.is_some() {
+            self.failed += 1;
+            return;
+        }
+        let unknown = || "unknown".to_string();
+        *self.by_producer.entry(row.producer.clone().unwrap_or_else(unknown)).or_default() += 1;
+        *self.by_pdf_version.entry(row.pdf_version.clone().unwrap_or_else(unknown)).or_default() += 1;
+    }
+
+    fn to_json(&self) -> Json {
+        let histogram = |map: &BTreeMap<String, usize>| {
+            Json::Object(map.iter().map(|(k, v)| (k.clone(), Json::Number(*v as u64))).collect())
+        };
+        Json::object(vec![("summary", Json::object(vec![
+            ("files", Json::Number(self.files as u64)),
+            ("failed", Json::Number(self.failed as u64)),
+            ("by_producer", histogram(&self.by_producer)),
+            ("by_pdf_version", histogram(&self.by_pdf_version)),
+            ("unreadable_dirs", Json::Number(self.unreadable_dirs as u64)),
+        ]))])
+    }
+
+    //A second table after an empty line: histogram,value,count
+    fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out)?;
+        writeln!(out, "histogram,value,count")?;
+        writeln!(out, "files,,{}", self.files)?;
+        writeln!(out, "failed,,{}", self.failed)?;
+        writeln!(out, "unreadable_dirs,,{}", self.unreadable_dirs)?;
+        for (producer, count) in &self.by_producer {
+            writeln!(out, "producer,{},{}", csv::field(producer), count)?;
+        }
+        for (version, count) in &self.by_pdf_version {
+            writeln!(out, "pdf_version,{},{}", csv::field(version), count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The pdf files found below a folder
+#[derive(Debug, Default)]
+pub struct PdfFiles {
+    pub pdfs: Vec<PathBuf>,
+    /// subfolders which could not be read, with the error
+    pub unreadable: Vec<(PathBuf, io::Error)>,
+}
+
+/// All files with a .pdf extension (any case) below the folder, sorted
+/// Symlinked folders are not followed, so links pointing back up can not make the walk loop.
+pub fn find_pdfs(root: &Path) -> io::Result<PdfFiles> {
+    let mut files = PdfFiles::default();
+    let mut dirs = vec![];
+    read_folder(root, &mut dirs, &mut files.pdfs)?;
+    while let Some(dir) = dirs.pop() {
+        if let Err(e) = read_folder(&dir, &mut dirs, &mut files.pdfs) {
+            files.unreadable.push((dir, e));
+        }
+    }
+    files.pdfs.sort();
+    files.unreadable.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+fn read_folder(dir: &Path, dirs: &mut Vec<PathBuf>, pdfs: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        //file_type does not follow symlinks, a symlink to a pdf file is still taken
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            dirs.push(path);
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")) && (file_type.is_file() || path.is_file()) {
+            pdfs.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Runs pdfinfo on every pdf below the folder and writes one row per file,
+/// followed by the summary (a last JSON line or a second CSV table)
+/// Subfolders which can not be read get a row with the error, only an unreadable root folder fails the report.
+pub fn corpus_report(tools: &XpdfTools, root: &Path, format: ReportFormat, out: &mut impl Write) -> io::Result<CorpusSummary> {
+    let mut summary = CorpusSummary::default();
+    if format == ReportFormat::Csv {
+        writeln!(out, "{}", CSV_HEADER)?;
+    }
+
+    let PdfFiles { pdfs, unreadable } = find_pdfs(root)?;
+    let folder_rows = unreadable.into_iter()
+        .map(|(dir, e)| CorpusRow { path: dir, error: Some(format!("Could not read the folder: {}", e)), ..Default::default() });
+    summary.unreadable_dirs = folder_rows.len();
+    let pdf_rows = pdfs.into_iter().map(|pdf| {
+        let row = match tools.pdf_info(&pdf) {
+            Ok(info) => CorpusRow::from_info(&pdf, &info),
+            Err(e) => CorpusRow { path: pdf.clone(), error: Some(e.to_string()), ..Default::default() },
+        };
+        summary.add(&row);
+        row
+    });
+
+    for row in folder_rows.chain(pdf_rows) {
+        match format {
+            ReportFormat::JsonLines => writeln!(out, "{}", row.to_json())?,
+            ReportFormat::Csv => writeln!(out, "{}", row.to_csv())?,
+        }
+    }
+
+    match format {
+        ReportFormat::JsonLines => writeln!(out, "{}", summary.to_json())?,
+        ReportFormat::Csv => summary.write_csv(out)?,
+    }
+    Ok(summary)
+}
+
+#[test]
+fn test_corpus_report() {
+    use std::fs;
+    use crate::runner::{FakeRunner, ToolOutput};
+
+    let root = std::env::temp_dir().join(format!("xpdf_corpus_{}", std::process::id()));
+    fs::create_dir_all(root.join("2024")).unwrap();
+    fs::write(root.join("a.pdf"), "").unwrap();
+    fs::write(root.join("2024").join("b.PDF"), "").unwrap();
+    fs::write(root.join("notes.txt"), "").unwrap();
+
+    let info = "Producer:       LibreOffice, 7.3\nPages:          2\nEncrypted:      no\nTagged:         yes\nFile size:      18436 bytes\nPDF version:    1.7\n";
+    let tools = crate::XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdfinfo"])
+        .runner(FakeRunner::new().with_output("pdfinfo", ToolOutput::ok(info)))
+        .build().unwrap();
+
+    let mut csv = vec![];
+    let summary = corpus_report(&tools, &root, ReportFormat::Csv, &mut csv).unwrap();
+    let mut jsonl = vec![];
+    corpus_report(&tools, &root, ReportFormat::JsonLines, &mut jsonl).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(summary.files, 2);
+    assert_eq!(summary.by_producer["LibreOffice, 7.3"], 2);
+    assert_eq!(summary.by_pdf_version["1.7"], 2);
+
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines[0], CSV_HEADER);
+    assert!(lines[1].contains("2024"));
+    assert!(lines[2].ends_with("a.pdf,2,1.7,\"LibreOffice, 7.3\",,false,true,,,18436,"));
+    assert!(csv.contains("\nproducer,\"LibreOffice, 7.3\",2\n"));
+
+    let jsonl = String::from_utf8(jsonl).unwrap();
+    assert_eq!(jsonl.lines().count(), 3);
+    assert!(jsonl.lines().last().unwrap().starts_with(r#"{"summary":{"files":2,"failed":0,"by_producer":{"LibreOffice, 7.3":2}"#));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_find_pdfs_links_and_unreadable_folders() {
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let root = std::env::temp_dir().join(format!("xpdf_corpus_walk_{}", std::process::id()));
+    let locked = root.join("locked");
+    fs::create_dir_all(&locked).unwrap();
+    fs::write(root.join("a.pdf"), "").unwrap();
+    fs::write(locked.join("b.pdf"), "").unwrap();
+    //a loop back to the root and a link to a pdf
+    symlink(&root, root.join("loop")).unwrap();
+    symlink(root.join("a.pdf"), root.join("link.pdf")).unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    //root reads every folder regardless of the permissions
+    let readable = locked.read_dir().is_ok();
+
+    let PdfFiles { pdfs, unreadable } = find_pdfs(&root).unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+
+    if readable {
+        assert_eq!(pdfs, vec![root.join("a.pdf"), root.join("link.pdf"), locked.join("b.pdf")]);
+        assert!(unreadable.is_empty());
+    } else {
+        assert_eq!(pdfs, vec![root.join("a.pdf"), root.join("link.pdf")]);
+        assert_eq!(unreadable.iter().map(|(dir, _)| dir.clone()).collect::<Vec<_>>(), vec![locked]);
+    }
+}
@@ -0,0 +1,20 @@
+//CSV writing shared by the corpus report and the table export
+
+/// The field, quoted if it contains a separator, a quote or a line break
+pub(crate) fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// The fields joined into one line, without line break
+pub(crate) fn row<S: AsRef<str>>(fields: &[S]) -> String {
+    fields.iter().map(|text| field(text.as_ref())).collect::<Vec<_>>().join(",")
+}
+
+#[test]
+fn test_csv_row() {
+    assert_eq!(row(&["a", "b,c", "say \"hi\"", "two\nlines"]), "a,\"b,c\",\"say \"\"hi\"\"\",\"two\nlines\"");
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::health::{self, ToolStatus};
+use crate::VALID_TOOLS;
+
+/// Environment variable pointing to a folder with the xpdf tools
+pub const TOOLS_DIR_ENV: &str = "XPDF_TOOLS_DIR";
+
+/// Install locations searched after XPDF_TOOLS_DIR and PATH
+pub const COMMON_PREFIXES: &[&str] = &[
+    "/usr/local/bin",
+    "/usr/bin",
+    "/opt/homebrew/bin",
+    "/opt/local/bin",
+    "/opt/xpdf/bin",
+    "C:\\Program Files\\xpdf-tools\\bin64",
+    "C:\\Program Files\\xpdf-tools\\bin32",
+];
+
+/// Where a search directory came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiscoverySource {
+    EnvVar,
+    Path,
+    CommonPrefix,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolLocation {
+    pub path: PathBuf,
+    pub source: DiscoverySource,
+}
+
+/// Result of a tool discovery, every tool is looked up on its own
+/// so tools may come from different directories
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiscoveryReport {
+    pub found: BTreeMap<String, ToolLocation>,
+    pub missing: Vec<String>,
+    /// the directories in search order
+    pub searched: Vec<(PathBuf, DiscoverySource)>,
+}
+
+impl DiscoveryReport {
+    pub fn is_empty(&self) -> bool {
+        self.found.is_empty()
+    }
+}
+
+/// Searches XPDF_TOOLS_DIR, every PATH entry and the common prefixes
+pub fn discover() -> DiscoveryReport {
+    discover_in(search_dirs(env::var_os(TOOLS_DIR_ENV), env::var_os("PATH")))
+}
+
+/// The directories to search in order, duplicates are dropped
+pub fn search_dirs(tools_dir: Option<OsString>, path: Option<OsString>) -> Vec<(PathBuf, DiscoverySource)> {
+    let mut dirs: Vec<(PathBuf, DiscoverySource)> = vec![];
+
+    if let Some(dir) = tools_dir.filter(|d| !d.is_empty()) {
+        dirs.push((PathBuf::from(dir), DiscoverySource::EnvVar));
+    }
+    if let Some(path) = path {
+        dirs.extend(env::split_paths(&path)
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(|p| (p, DiscoverySource::Path)));
+    }
+    dirs.extend(COMMON_PREFIXES.iter().map(|p| (PathBuf::from(p), DiscoverySource::CommonPrefix)));
+
+    let mut unique: Vec<(PathBuf, DiscoverySource)> = vec![];
+    for (dir, source) in dirs {
+        if !unique.iter().any(|(d, _)| *d == dir) {
+            unique.push((dir, source));
+        }
+    }
+    unique
+}
+
+/// Looks up every tool in the given directories, the first hit wins
+pub fn discover_in(dirs: Vec<(PathBuf, DiscoverySource)>) -> DiscoveryReport {
+    let mut report = DiscoveryReport::default();
+
+    for tool in VALID_TOOLS {
+        let location = dirs.iter().find_map(|(dir, source)| {
+            find_tool(dir, tool).map(|path| ToolLocation { path, source: *source })
+        });
+        match location {
+            Some(location) => { report.found.insert(tool.to_string(), location); },
+            None => report.missing.push(tool.to_string()),
+        }
+    }
+    report.searched = dirs;
+    report
+}
+
+//The path of a usable tool binary, like `which` a file without execute bit does not hide one further along
+fn find_tool(dir: &Path, tool: &str) -> Option<PathBuf> {
+    health::tool_file_names(tool).into_iter()
+        .map(|name| dir.join(name))
+        .find(|path| health::check_file(path) == ToolStatus::Accepted)
+}
+
+#[test]
+fn test_search_dirs() {
+    let path = env::join_paths(["/a/bin", "/b/bin", "/a/bin"]).unwrap();
+    let dirs = search_dirs(Some("/xpdf".into()), Some(path));
+
+    assert_eq!(dirs[0], (PathBuf::from("/xpdf"), DiscoverySource::EnvVar));
+    assert_eq!(dirs[1], (PathBuf::from("/a/bin"), DiscoverySource::Path));
+    assert_eq!(dirs[2], (PathBuf::from("/b/bin"), DiscoverySource::Path));
+    assert_eq!(dirs[3], (PathBuf::from(COMMON_PREFIXES[0]), DiscoverySource::CommonPrefix));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_discover_in() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = env::temp_dir().join(format!("xpdf_discover_{}", std::process::id()));
+    let (first, second) = (root.join("first"), root.join("second"));
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+    let executable = |path: PathBuf| {
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    };
+    executable(first.join("pdfinfo"));
+    executable(second.join("pdfinfo"));
+    //not executable, the one in the second folder is taken
+    fs::write(first.join("pdftotext"), "").unwrap();
+    executable(second.join("pdftotext"));
+    //only the bare name counts on unix
+    executable(first.join("pdffonts.exe"));
+
+    let report = discover_in(vec![(first.clone(), DiscoverySource::EnvVar), (second.clone(), DiscoverySource::Path)]);
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(report.found["pdfinfo"], ToolLocation { path: first.join("pdfinfo"), source: DiscoverySource::EnvVar });
+    assert_eq!(report.found["pdftotext"], ToolLocation { path: second.join("pdftotext"), source: DiscoverySource::Path });
+    assert!(report.missing.contains(&"pdffonts".to_string()));
+}
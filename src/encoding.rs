@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use crate::version::ToolBackend;
+use crate::{trace, PdfError, PdfErrorKind};
+
+//(pdftotext path, xpdfrc contents) -> encodings, an xpdfrc can add encodings with unicodeMap
+type EncodingCache = HashMap<(PathBuf, Option<String>), Vec<String>>;
+
+static ENCODINGS: OnceLock<Mutex<EncodingCache>> = OnceLock::new();
+
+/// Parses the output of `pdftotext -listencodings` (xpdf) or `-listenc` (poppler)
+/// Both print a heading line ending with a colon followed by one name per line
+pub fn parse_encodings(raw: &str) -> Vec<String> {
+    raw.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.ends_with(':'))
+        .map(String::from)
+        .collect()
+}
+
+pub(crate) fn cached(key: &(PathBuf, Option<String>)) -> Option<Vec<String>> {
+    ENCODINGS.get_or_init(Default::default).lock().ok()?.get(key).cloned()
+}
+
+pub(crate) fn store(key: (PathBuf, Option<String>), encodings: Vec<String>) {
+    if let Ok(mut cache) = ENCODINGS.get_or_init(Default::default).lock() {
+        cache.insert(key, encodings);
+    }
+}
+
+/// The encoding a tool writes without `-enc`: xpdf's default textEncoding is Latin1, poppler writes UTF-8
+pub fn default_encoding(backend: ToolBackend) -> &'static str {
+    match backend {
+        ToolBackend::Xpdf => "Latin1",
+        ToolBackend::Poppler => "UTF-8",
+    }
+}
+
+/// Decodes tool output written with `-enc <encoding>`, a byte order mark at the start is removed
+/// UTF-8, ASCII7, Latin1 and UCS-2 (big endian, as xpdf writes it) are always supported,
+/// other encodings added with an xpdfrc unicodeMap need the `encoding` feature.
+/// Undecodable bytes fail with their count instead of being replaced.
+pub fn decode(bytes: &[u8], encoding: &str) -> Result<String, PdfError> {
+    match decode_lossy(bytes, encoding)? {
+        (text, 0) => Ok(text),
+        (_, replaced) => Err(undecodable(encoding, replaced)),
+    }
+}
+
+/// Like `decode`, undecodable byte sequences are replaced by U+FFFD, returns the text and their count
+/// Fails only for encodings without a decoder
+pub fn decode_lossy(bytes: &[u8], encoding: &str) -> Result<(String, usize), PdfError> {
+    let name = encoding.to_lowercase();
+    match name.as_str() {
+        "utf-8" | "utf8" => {
+            let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+            let invalid = bytes.utf8_chunks().filter(|chunk| !chunk.invalid().is_empty()).count();
+            Ok((String::from_utf8_lossy(bytes).into_owned(), invalid))
+        },
+        "ascii7" => {
+            let text = bytes.iter().map(|b| if b.is_ascii() { *b as char } else { char::REPLACEMENT_CHARACTER }).collect();
+            Ok((text, bytes.iter().filter(|b| !b.is_ascii()).count()))
+        },
+        //ISO-8859-1 maps every byte to the code point of the same value
+        "latin1" => Ok((bytes.iter().map(|b| *b as char).collect(), 0)),
+        "ucs-2" => {
+            let bytes = bytes.strip_prefix(b"\xfe\xff").unwrap_or(bytes);
+            let units = bytes.chunks(2).map(|pair| match pair {
+                [high, low] => u16::from_be_bytes([*high, *low]),
+                //a lone trailing byte can never be decoded
+                _ => 0xdc00,
+            });
+            let mut invalid = 0;
+            let text = char::decode_utf16(units).map(|c| c.unwrap_or_else(|_| {
+                invalid += 1;
+                char::REPLACEMENT_CHARACTER
+            })).collect();
+            Ok((text, invalid))
+        },
+        //the bytes are character codes of the Symbol and ZapfDingbats fonts, not text
+        "symbol" | "zapfdingbats" => Err(PdfError::new(PdfErrorKind::OutputDecodingError, format!("{} output has no unicode decoding, use the raw bytes", encoding), "".into())),
+        _ => decode_other(bytes, encoding),
+    }
+}
+
+#[cfg(feature = "encoding")]
+fn decode_other(bytes: &[u8], encoding: &str) -> Result<(String, usize), PdfError> {
+    let Some(decoder) = encoding_rs::Encoding::for_label(encoding.as_bytes()) else {
+        return Err(PdfError::new(PdfErrorKind::OutputDecodingError, format!("No decoder for the {} encoding", encoding), "".into()));
+    };
+    let (text, _, had_errors) = decoder.decode(bytes);
+    let replaced = if had_errors { text.matches('\u{fffd}').count() } else { 0 };
+    Ok((text.into_owned(), replaced))
+}
+
+#[cfg(not(feature = "encoding"))]
+fn decode_other(_bytes: &[u8], encoding: &str) -> Result<(String, usize), PdfError> {
+    Err(PdfError::new(PdfErrorKind::OutputDecodingError, format!("Decoding {} output needs the encoding feature", encoding), "".into()))
+}
+
+//Decodes the output of a call leniently, one stray byte must not fail e.g. pdf_info on its XMP metadata
+//The number of replaced bytes is reported as tracing event
+pub(crate) fn decode_output(bytes: &[u8], encoding: &str) -> Result<String, PdfError> {
+    let (text, replaced) = decode_lossy(bytes, encoding)?;
+    if replaced > 0 {
+        trace::replaced(encoding, replaced);
+    }
+    Ok(text)
+}
+
+fn undecodable(encoding: &str, count: usize) -> PdfError {
+    PdfError::new(PdfErrorKind::OutputDecodingError, format!("{} undecodable byte sequences in the {} output", count, encoding), "".into())
+}
+
+/// Fails unless the encoding is one of the available ones, the error suggests a near match like UTF-8 for UTF8
+pub fn check_encoding(name: &str, available: &[String]) -> Result<(), PdfError> {
+    if available.iter().any(|encoding| encoding == name) {
+        return Ok(());
+    }
+    let simplified = |name: &str| name.to_lowercase().replace(['-', '_', ' '], "");
+    let suggestion = available.iter()
+        .find(|encoding| simplified(encoding) == simplified(name))
+        .map(|encoding| format!(", did you mean {}?", encoding))
+        .unwrap_or_default();
+
+    Err(PdfError::new(PdfErrorKind::UnsupportedArgument, format!("Unknown encoding {}{}", name, suggestion), format!("available encodings: {}", available.join(", "))))
+}
+
+#[test]
+fn test_parse_encodings() {
+    let xpdf = "Available encodings:\n  Latin1\n  ASCII7\n  Symbol\n  ZapfDingbats\n  UTF-8\n  UCS-2\n";
+    let poppler = "Available encodings are:\nUCS-2\nASCII7\nLatin1\nUTF-8\nZapfDingbats\nSymbol\n";
+    assert_eq!(parse_encodings(xpdf), vec!["Latin1", "ASCII7", "Symbol", "ZapfDingbats", "UTF-8", "UCS-2"]);
+    assert_eq!(parse_encodings(poppler).len(), 6);
+
+    let available = parse_encodings(xpdf);
+    assert!(check_encoding("UTF-8", &available).is_ok());
+    let error = check_encoding("utf8", &available).unwrap_err();
+    assert_eq!(error.message, "Unknown encoding utf8, did you mean UTF-8?");
+    assert_eq!(check_encoding("KOI8-R", &available).unwrap_err().message, "Unknown encoding KOI8-R");
+}
+
+#[test]
+fn test_decode() {
+    assert_eq!(decode(b"\xef\xbb\xbfGr\xc3\xbc\xc3\x9fe", "UTF-8").unwrap(), "Grüße");
+    assert_eq!(decode(b"Gr\xfc\xdfe", "Latin1").unwrap(), "Grüße");
+    assert_eq!(decode(b"\xfe\xff\x00G\x00r\x00\xfc", "UCS-2").unwrap(), "Grü");
+    assert_eq!(decode(b"Gr\xfc\xdfe", "UTF-8").unwrap_err().message, "2 undecodable byte sequences in the UTF-8 output");
+    assert_eq!(decode(b"Gr\xfc\xdfe", "ASCII7").unwrap_err().kind(), PdfErrorKind::OutputDecodingError);
+    assert!(decode(b"\x00G\x00", "UCS-2").is_err());
+    assert!(decode(b"abc", "ZapfDingbats").is_err());
+    assert_eq!(decode_lossy(b"Gr\xfc\xdfe", "ASCII7").unwrap(), ("Gr\u{fffd}\u{fffd}e".to_string(), 2));
+    assert_eq!(decode_lossy(b"Gr\xfc", "UTF-8").unwrap(), ("Gr\u{fffd}".to_string(), 1));
+    assert_eq!(decode_output(b"Gr\xfc\xdfe", default_encoding(ToolBackend::Xpdf)).unwrap(), "Grüße");
+    assert_eq!(decode_output(b"\x00G\x00", "UCS-2").unwrap(), "G\u{fffd}");
+
+    #[cfg(feature = "encoding")]
+    assert_eq!(decode(b"\xd0\xd2\xc9\xd7\xc5\xd4", "KOI8-R").unwrap(), "привет");
+    #[cfg(not(feature = "encoding"))]
+    assert!(decode(b"abc", "KOI8-R").is_err());
+}
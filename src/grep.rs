@@ -0,0 +1,96 @@
+use std::path::{Path, PathBuf};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::xpdf_text::PdfText;
+use crate::{PdfError, PdfErrorKind};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GrepOptions {
+    pub ignore_case: bool,
+    /// the match must start and end at a word boundary
+    pub whole_word: bool,
+    /// stop after this many matching lines per file
+    pub max_matches: Option<usize>,
+    /// lines before and after a match, taken from the same page
+    pub context: usize,
+}
+
+/// A matching line, page and line numbers start at 1, the line number counts within the page
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GrepMatch {
+    pub path: PathBuf,
+    pub page: usize,
+    pub line: usize,
+    pub text: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// The matches of a grep over several files and the files which could not be searched
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GrepResults {
+    pub matches: Vec<GrepMatch>,
+    /// files whose text could not be extracted, e.g. broken or encrypted ones
+    pub errors: Vec<(PathBuf, PdfError)>,
+}
+
+/// The regex with the case and word options applied
+pub fn grep_regex(regex: &Regex, options: &GrepOptions) -> Result<Regex, PdfError> {
+    let pattern = match options.whole_word {
+        true => format!(r"\b(?:{})\b", regex.as_str()),
+        false => regex.as_str().to_string(),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(options.ignore_case)
+        .build()
+        .map_err(|e| PdfError::new(PdfErrorKind::InvalidQuery, format!("Invalid grep pattern: {}", regex.as_str()), e.to_string()))
+}
+
+/// The matching lines of extracted text, the regex must already have the options applied
+pub fn grep_text(path: &Path, text: &PdfText, regex: &Regex, options: &GrepOptions) -> Vec<GrepMatch> {
+    let mut matches = vec![];
+    for (page, page_text) in text.numbered_pages() {
+        let lines: Vec<&str> = page_text.lines().collect();
+
+        for (i, line) in lines.iter().enumerate().filter(|(_, line)| regex.is_match(line)) {
+            if options.max_matches.is_some_and(|max| matches.len() >= max) {
+                return matches;
+            }
+            matches.push(GrepMatch {
+                path: path.into(),
+                page,
+                line: i + 1,
+                text: line.to_string(),
+                before: lines[i.saturating_sub(options.context)..i].iter().map(|l| l.to_string()).collect(),
+                after: lines[i + 1..(i + 1 + options.context).min(lines.len())].iter().map(|l| l.to_string()).collect(),
+            });
+        }
+    }
+    matches
+}
+
+#[test]
+fn test_grep_text() {
+    let text = PdfText { first_page: 2, pages: vec![
+        "Termination\nEither party may terminate\nwith written notice.\n".into(),
+        "NOTICE period\nnoticeable\n".into(),
+    ]};
+    let path = Path::new("contract.pdf");
+    let grep = |options: GrepOptions| {
+        let regex = grep_regex(&Regex::new("notice").unwrap(), &options).unwrap();
+        grep_text(path, &text, &regex, &options).into_iter().map(|m| (m.page, m.line)).collect::<Vec<_>>()
+    };
+
+    assert_eq!(grep(GrepOptions::default()), vec![(2, 3), (3, 2)]);
+    assert_eq!(grep(GrepOptions { ignore_case: true, ..Default::default() }), vec![(2, 3), (3, 1), (3, 2)]);
+    assert_eq!(grep(GrepOptions { ignore_case: true, whole_word: true, ..Default::default() }), vec![(2, 3), (3, 1)]);
+    assert_eq!(grep(GrepOptions { ignore_case: true, max_matches: Some(2), ..Default::default() }), vec![(2, 3), (3, 1)]);
+
+    let options = GrepOptions { context: 1, ..Default::default() };
+    let regex = grep_regex(&Regex::new("terminate").unwrap(), &options).unwrap();
+    let found = grep_text(path, &text, &regex, &options);
+    assert_eq!(found[0].before, vec!["Termination"]);
+    assert_eq!(found[0].after, vec!["with written notice."]);
+}
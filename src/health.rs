@@ -0,0 +1,137 @@
+use core::fmt;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Outcome of checking a tool candidate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToolStatus {
+    Accepted,
+    /// the path is a directory or another non regular file
+    NotAFile,
+    /// a symlink whose target does not exist
+    BrokenSymlink,
+    /// the executable bit is not set
+    NotExecutable,
+    /// `<tool> -v` could not be run or printed no version banner
+    SmokeTestFailed,
+}
+
+impl fmt::Display for ToolStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            ToolStatus::Accepted => "accepted",
+            ToolStatus::NotAFile => "not a regular file",
+            ToolStatus::BrokenSymlink => "broken symlink",
+            ToolStatus::NotExecutable => "not executable",
+            ToolStatus::SmokeTestFailed => "smoke test with -v failed",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCheck {
+    pub tool: String,
+    pub path: PathBuf,
+    pub status: ToolStatus,
+}
+
+/// Why each tool candidate was accepted or rejected by the builder
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolReport {
+    pub checks: Vec<ToolCheck>,
+}
+
+impl ToolReport {
+    pub fn accepted(&self) -> impl Iterator<Item = &ToolCheck> {
+        self.checks.iter().filter(|c| c.status == ToolStatus::Accepted)
+    }
+    pub fn rejected(&self) -> impl Iterator<Item = &ToolCheck> {
+        self.checks.iter().filter(|c| c.status != ToolStatus::Accepted)
+    }
+    /// At least one tool is usable and no candidate was rejected
+    pub fn is_healthy(&self) -> bool {
+        self.accepted().next().is_some() && self.rejected().next().is_none()
+    }
+
+    pub(crate) fn push(&mut self, tool: &str, path: &Path, status: ToolStatus) {
+        self.checks.push(ToolCheck { tool: tool.into(), path: path.into(), status });
+    }
+    pub(crate) fn reject(&mut self, tool: &str, status: ToolStatus) {
+        for check in self.checks.iter_mut().filter(|c| c.tool == tool) {
+            check.status = status;
+        }
+    }
+}
+
+impl fmt::Display for ToolReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "{} ({}): {}", check.tool, check.path.display(), check.status)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the path is an existing, executable regular file
+pub fn check_file(path: &Path) -> ToolStatus {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => {
+            return match fs::symlink_metadata(path) {
+                Ok(_) => ToolStatus::BrokenSymlink,
+                Err(_) => ToolStatus::NotAFile,
+            }
+        }
+    };
+    if !metadata.is_file() {
+        return ToolStatus::NotAFile;
+    }
+    if !is_executable(path, &metadata) {
+        return ToolStatus::NotExecutable;
+    }
+    ToolStatus::Accepted
+}
+
+/// The file names a tool binary can have in a folder: the bare name, on windows the .exe
+pub fn tool_file_names(tool: &str) -> Vec<String> {
+    if cfg!(unix) {
+        vec![tool.to_string()]
+    } else {
+        vec![format!("{}.exe", tool)]
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(_path: &Path, metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+//Windows has no executable bit, only the extensions it runs directly count
+#[cfg(not(unix))]
+fn is_executable(path: &Path, _metadata: &fs::Metadata) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| ["exe", "com", "bat", "cmd"].iter().any(|e| extension.eq_ignore_ascii_case(e)))
+}
+
+#[cfg(unix)]
+#[test]
+fn test_check_file() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let root = std::env::temp_dir().join(format!("xpdf_health_{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let (text, exec, link) = (root.join("pdfinfo"), root.join("pdftotext"), root.join("pdffonts"));
+    fs::write(&text, "not a binary").unwrap();
+    fs::write(&exec, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&exec, fs::Permissions::from_mode(0o755)).unwrap();
+    symlink(root.join("missing"), &link).unwrap();
+
+    let statuses = (check_file(&text), check_file(&exec), check_file(&link), check_file(&root));
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(statuses, (ToolStatus::NotExecutable, ToolStatus::Accepted, ToolStatus::BrokenSymlink, ToolStatus::NotAFile));
+}
@@ -1,53 +1,233 @@
 use core::fmt;
 
+use std::collections::BTreeMap;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use regex::Regex;
 
 
 
 pub mod xpdf_info;
 pub mod xpdf_text;
+pub mod xpdf_fonts;
+pub mod xpdf_images;
+pub mod xpdf_detach;
+pub mod xpdf_render;
 pub mod types;
+pub mod password;
+pub mod version;
+pub mod discover;
+pub mod health;
+pub mod runner;
+pub mod json;
+mod csv;
+pub mod corpus;
+pub mod cache;
+pub mod search;
+pub mod grep;
+pub mod table;
+pub mod clean;
+pub mod config;
+pub mod encoding;
+mod trace;
+pub mod secret;
+pub mod limits;
+pub mod sandbox;
 
 use xpdf_info::PdfInfo;
+use xpdf_text::PdfText;
+use xpdf_fonts::PdfFonts;
+use xpdf_images::PdfImages;
+use xpdf_detach::PdfAttachment;
+use discover::DiscoveryReport;
+use health::{ToolReport, ToolStatus};
+use runner::{ProcessRunner, ToolOutput, ToolRunner};
+use cache::ResultCache;
+use config::{ConfigFile, XpdfConfig};
+use limits::{OutputFiles, OutputLimit, OutputOverflow, ResourceLimits};
+use sandbox::SandboxPolicy;
+use grep::{GrepOptions, GrepResults};
+use table::Table;
+use password::{PasswordProvider, PdfPassword};
+use version::{ToolBackend, ToolVersion};
 
 use types::XpdfArgs;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PdfErrorKind {
     NoXpdfToolsDirectory,
     ToolNotAvailable,
     PdfInfoError,
     PdfToTextError,
     PdfToTextErrorNoOutput,
+    PdfFontsError,
+    PdfImagesError,
+    PdfDetachError,
+    PdfRenderError,
+    PasswordRequired,
+    UnsupportedArgument,
+    ToolNotUsable,
+    InvalidQuery,
+    ConfigError,
+    OutputDecodingError,
+    ResourceLimitExceeded,
+    OutputLimitExceeded,
 }
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PdfError {
     pub message: String,
     pub process_message: String,
     pub error_kind: PdfErrorKind,
+    //boxed to keep Result<_, PdfError> small
+    command: Option<Box<ToolCommand>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    source: Option<Arc<io::Error>>,
+}
+
+/// A tool invocation as recorded in a PdfError
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ToolCommand {
+    pub tool: String,
+    pub program: PathBuf,
+    /// the arguments with the values of -opw and -upw replaced by ***
+    pub args: Vec<String>,
+    /// None if the process did not start or was killed by a signal
+    pub status: Option<i32>,
+    pub stderr: String,
+}
+
+impl ToolCommand {
+    pub(crate) fn new(tool: &str, program: PathBuf, args: &[String], output: Option<&ToolOutput>) -> Self {
+        ToolCommand {
+            tool: tool.into(),
+            program,
+            args: redact_args(args),
+            status: output.and_then(|o| o.status),
+            stderr: output.map(|o| String::from_utf8_lossy(&o.stderr).into_owned()).unwrap_or_default(),
+        }
+    }
+}
+
+fn is_password_flag(arg: &str) -> bool {
+    arg == "-opw" || arg == "-upw"
+}
+
+//The values of -opw and -upw replaced by ***
+pub(crate) fn redact_args(args: &[String]) -> Vec<String> {
+    args.iter().enumerate()
+        .map(|(i, arg)| match i.checked_sub(1).map(|prev| args[prev].as_str()) {
+            Some(prev) if is_password_flag(prev) => secret::REDACTED.to_string(),
+            _ => arg.clone(),
+        })
+        .collect()
+}
+
+impl fmt::Display for ToolCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program.display())?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        match self.status {
+            Some(status) => write!(f, " (exit status {})", status),
+            None => Ok(()),
+        }
+    }
 }
 
-#[allow(dead_code)]
 impl PdfError {
-    fn kind(&self) -> PdfErrorKind {
+    pub fn new(error_kind: PdfErrorKind, message: String, process_message: String) -> Self {
+        PdfError { message, process_message, error_kind, command: None, source: None }
+    }
+
+    pub fn kind(&self) -> PdfErrorKind {
         self.error_kind
     }
+
+    /// The tool invocation which failed, None for errors before or without a process
+    pub fn command(&self) -> Option<&ToolCommand> {
+        self.command.as_deref()
+    }
+
+    pub(crate) fn with_command(mut self, command: ToolCommand) -> Self {
+        self.command = Some(Box::new(command));
+        self
+    }
+
+    pub(crate) fn with_source(mut self, source: io::Error) -> Self {
+        self.source = Some(Arc::new(source));
+        self
+    }
+
+    fn password_required(pdf_file: &Path, stderr: &[u8]) -> Self {
+        PdfError::new(PdfErrorKind::PasswordRequired, format!("Incorrect or missing password: {:?}", pdf_file), String::from_utf8_lossy(stderr).to_string())
+    }
 }
+
+//The io source is compared by its kind, io::Error has no equality
+impl PartialEq for PdfError {
+    fn eq(&self, other: &Self) -> bool {
+        self.message == other.message
+            && self.process_message == other.process_message
+            && self.error_kind == other.error_kind
+            && self.command == other.command
+            && self.source.as_ref().map(|e| e.kind()) == other.source.as_ref().map(|e| e.kind())
+    }
+}
+
 impl fmt::Display for PdfError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.message)
     }
 }
-impl std::error::Error for PdfError {}
 
-const VALID_TOOLS: &'static [&'static str] = &["pdfdetach", "pdffonts", "pdfimages", "pdfinfo", "pdftohtml", "pdftopng", "pdftoppm", "pdftops", "pdftotext"];
+impl std::error::Error for PdfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_deref().map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
+
+const VALID_TOOLS: &[&str] = &["pdfdetach", "pdffonts", "pdfimages", "pdfinfo", "pdftohtml", "pdftopng", "pdftoppm", "pdftops", "pdftotext"];
 
-#[derive(Debug)]
 pub struct XpdfTools {
     tools_folder: PathBuf,
     extra_args: Option<Vec<XpdfArgs>>,
     available_tools: Option<Vec<String>>,
+    tool_paths: BTreeMap<String, PathBuf>,
+    tool_versions: BTreeMap<String, ToolVersion>,
+    tool_report: ToolReport,
+    discovery: Option<DiscoveryReport>,
+    password_provider: Option<Box<dyn PasswordProvider>>,
+    runner: Arc<dyn ToolRunner>,
+    cache: Option<ResultCache>,
+    config_file: Option<ConfigFile>,
+    limits: ResourceLimits,
+    sandbox: Option<SandboxPolicy>,
+    max_output: Option<(u64, OutputOverflow)>,
+}
 
+impl fmt::Debug for XpdfTools {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XpdfTools")
+            .field("tools_folder", &self.tools_folder)
+            .field("extra_args", &self.extra_args)
+            .field("available_tools", &self.available_tools)
+            .field("tool_paths", &self.tool_paths)
+            .field("tool_versions", &self.tool_versions)
+            .field("password_provider", &self.password_provider.is_some())
+            .field("cache", &self.cache)
+            .field("config_file", &self.config_file.as_ref().map(ConfigFile::path))
+            .field("limits", &self.limits)
+            .field("sandbox", &self.sandbox)
+            .field("max_output", &self.max_output)
+            .finish()
+    }
 }
 
 impl XpdfTools {
@@ -55,28 +235,103 @@ impl XpdfTools {
         XpdfToolsBuilder::new(tools_folder)
     }
 
+    /// A builder which takes the given tools as available without looking at the file system
+    /// Meant for custom runners, e.g. a FakeRunner in tests or tools executed in a container
+    pub fn builder_unchecked(tools_folder: PathBuf, tools: &[&str]) -> XpdfToolsBuilder {
+        XpdfToolsBuilder::unchecked(tools_folder, tools)
+    }
+
+    /// Looks up the tools in XPDF_TOOLS_DIR, the PATH entries and common install prefixes
+    /// Each tool is taken from the first directory containing it
+    pub fn discover() -> Result<XpdfToolsBuilder, PdfError> {
+        XpdfToolsBuilder::from_discovery(discover::discover())
+    }
+
     pub fn pdf_info(&self, pdf_file: &Path) -> Result<PdfInfo, PdfError> {
 
         match Self::pdf_check_tool(self, "pdfinfo") {
-            Ok(_) => xpdf_info::pdf_info(pdf_file, &self),
+            Ok(_) => self.with_passwords(pdf_file, |password| xpdf_info::pdf_info_with_password(pdf_file, self, password)),
             Err(e) => Err(e),
         }
     }
     pub fn pdf_text(&self, pdf_file: &Path) -> Result<Vec<u8>, PdfError> {
        
         match Self::pdf_check_tool(self, "pdftotext") {
-            Ok(_) =>  xpdf_text::pdf_to_binary(pdf_file, &self),
+            Ok(_) => self.with_passwords(pdf_file, |password| xpdf_text::pdf_to_binary_with_password(pdf_file, self, password)),
             Err(e) => Err(e),
         }
     }
     pub fn pdf_text_as_string(&self, pdf_file: &Path) -> Result<String, PdfError> {
        
         match Self::pdf_check_tool(self, "pdftotext") {
-            Ok(_) =>  xpdf_text::pdf_to_text(pdf_file, &self),
+            Ok(_) => self.with_passwords(pdf_file, |password| xpdf_text::pdf_to_text_with_password(pdf_file, self, password)),
             Err(e) => Err(e),
         }
     }
 
+    //Lets pdftotext write the text file, replaced atomically: a failed run leaves out_path as it was
+    pub fn pdf_text_to_file(&self, pdf_file: &Path, out_path: &Path) -> Result<(), PdfError> {
+        self.with_passwords(pdf_file, |password| xpdf_text::pdf_text_to_file_with_password(pdf_file, out_path, self, password))
+    }
+
+    //Streams the raw text into the writer as pdftotext writes it, returns the number of bytes
+    //A failing run can leave part of the text in the writer
+    pub fn pdf_text_to_writer(&self, pdf_file: &Path, out: &mut impl io::Write) -> Result<u64, PdfError> {
+        self.with_passwords(pdf_file, |password| xpdf_text::pdf_text_to_writer_with_password(pdf_file, out, self, password))
+    }
+
+    //The text split into pages at the page breaks
+    pub fn pdf_text_pages(&self, pdf_file: &Path) -> Result<PdfText, PdfError> {
+        let text = self.pdf_text_as_string(pdf_file)?;
+        Ok(PdfText::parse(&text, self.extra_args.as_deref().unwrap_or_default()))
+    }
+
+    //The lines of the extracted text matching the regex, in the order of the paths
+    //A file which fails is skipped and returned with its error, only an invalid regex fails the call
+    pub fn grep<P: AsRef<Path>>(&self, paths: &[P], regex: &Regex, options: GrepOptions) -> Result<GrepResults, PdfError> {
+        let regex = grep::grep_regex(regex, &options)?;
+        let mut results = GrepResults::default();
+        for path in paths.iter().map(AsRef::as_ref) {
+            match self.pdf_text_pages(path) {
+                Ok(text) => results.matches.extend(grep::grep_text(path, &text, &regex, &options)),
+                Err(e) => results.errors.push((path.into(), e)),
+            }
+        }
+        Ok(results)
+    }
+
+    //The tables of every page, see table::detect_tables
+    pub fn pdf_tables(&self, pdf_file: &Path) -> Result<Vec<Table>, PdfError> {
+        self.pdf_check_tool("pdftotext")?;
+        self.with_passwords(pdf_file, |password| table::pdf_tables_with_password(pdf_file, self, password))
+    }
+
+    pub fn pdf_fonts(&self, pdf_file: &Path) -> Result<PdfFonts, PdfError> {
+        self.with_passwords(pdf_file, |password| xpdf_fonts::pdf_fonts_with_password(pdf_file, self, password))
+    }
+
+    //The images as listed by pdfimages -list
+    pub fn pdf_images(&self, pdf_file: &Path) -> Result<PdfImages, PdfError> {
+        self.with_passwords(pdf_file, |password| xpdf_images::pdf_images_with_password(pdf_file, self, password))
+    }
+
+    pub fn pdf_images_extract(&self, pdf_file: &Path, out_dir: &Path) -> Result<Vec<PathBuf>, PdfError> {
+        self.with_passwords(pdf_file, |password| xpdf_images::pdf_images_extract_with_password(pdf_file, out_dir, self, password))
+    }
+
+    pub fn pdf_attachments(&self, pdf_file: &Path) -> Result<Vec<PdfAttachment>, PdfError> {
+        self.with_passwords(pdf_file, |password| xpdf_detach::pdf_attachments_with_password(pdf_file, self, password))
+    }
+
+    pub fn pdf_attachments_save(&self, pdf_file: &Path, out_dir: &Path) -> Result<Vec<PathBuf>, PdfError> {
+        self.with_passwords(pdf_file, |password| xpdf_detach::pdf_attachments_save_with_password(pdf_file, out_dir, self, password))
+    }
+
+    //Renders every page as image into the folder, see xpdf_render for the tool used
+    pub fn pdf_render(&self, pdf_file: &Path, out_dir: &Path, dpi: u32) -> Result<Vec<PathBuf>, PdfError> {
+        self.with_passwords(pdf_file, |password| xpdf_render::pdf_render_with_password(pdf_file, out_dir, dpi, self, password))
+    }
+
     pub fn pdf_check_tool(&self, tool: &str) -> Result<bool, PdfError> {
         if self.available_tools.as_ref().unwrap().contains(&tool.to_string()) {
             Ok(true)
@@ -84,19 +339,367 @@ impl XpdfTools {
             Err(Self::tool_not_available(tool))
         }
     }
-    fn tool_not_available(tool: &str) -> PdfError {
-        PdfError {
-            message: format!("{} not found in tools folder", tool),
-            process_message: "".into(),
-            error_kind: PdfErrorKind::ToolNotAvailable,
+    //Runs a tool on the pdf file and fails on a non zero exit status
+    //Options go before the file, trailing args (like an output root) after it
+    fn run_pdf_tool(&self, tool: &str, pdf_file: &Path, password: Option<&PdfPassword>, options: &[String], trailing: &[String], error_kind: PdfErrorKind) -> Result<ToolOutput, PdfError> {
+        self.run_pdf_tool_with(None, tool, pdf_file, password, options, trailing, error_kind)
+    }
+
+    //Like run_pdf_tool, never cached because the tool writes files, the files count for the output limit
+    #[allow(clippy::too_many_arguments)]
+    fn run_pdf_tool_to_files(&self, tool: &str, pdf_file: &Path, password: Option<&PdfPassword>, options: &[String], trailing: &[String], files: OutputFiles, error_kind: PdfErrorKind) -> Result<ToolOutput, PdfError> {
+        self.run_pdf_tool_with(Some(&files), tool, pdf_file, password, options, trailing, error_kind)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run_pdf_tool_with(&self, files: Option<&OutputFiles>, tool: &str, pdf_file: &Path, password: Option<&PdfPassword>, options: &[String], trailing: &[String], error_kind: PdfErrorKind) -> Result<ToolOutput, PdfError> {
+        self.pdf_check_tool(tool)?;
+
+        let mut args = self.tool_args(tool, password)?;
+        args.extend_from_slice(options);
+        args.push(self.path_arg(pdf_file));
+        args.extend_from_slice(trailing);
+
+        let output = match files {
+            None => self.run_tool_cached(tool, pdf_file, &args),
+            Some(files) => self.run_tool(tool, Some(pdf_file), Some(files), &args),
+        };
+        self.tool_result(tool, pdf_file, &args, output, error_kind)
+    }
+
+    //Like run_pdf_tool, the stdout of the tool goes into the writer as it arrives, never cached
+    //Returns the number of bytes written, a failing tool can leave part of its output in the writer
+    fn run_pdf_tool_streamed(&self, tool: &str, pdf_file: &Path, password: Option<&PdfPassword>, trailing: &[String], out: &mut dyn io::Write, error_kind: PdfErrorKind) -> Result<u64, PdfError> {
+        self.pdf_check_tool(tool)?;
+
+        let mut args = self.tool_args(tool, password)?;
+        args.push(self.path_arg(pdf_file));
+        args.extend_from_slice(trailing);
+
+        let limit = self.max_output.map(|(max_bytes, _)| OutputLimit { max_bytes, files: None });
+        let mut written = 0;
+        let output = trace::run_streamed(self.runner.as_ref(), &self.tool_path(tool), Some(pdf_file), &args, limit.as_ref(), out)
+            .map(|(o, streamed)| {
+                written = streamed;
+                o
+            });
+        self.tool_result(tool, pdf_file, &args, output, error_kind).map(|_| written)
+    }
+
+    //Fails for a truncated output with OutputOverflow::Fail, a non zero exit status or an error spawning the tool
+    fn tool_result(&self, tool: &str, pdf_file: &Path, args: &[String], output: io::Result<ToolOutput>, error_kind: PdfErrorKind) -> Result<ToolOutput, PdfError> {
+        match output {
+            Ok(o) if o.truncated => match self.overflow_error(tool, pdf_file, args, &o) {
+                Some(e) => Err(e),
+                None => Ok(o),
+            },
+            Ok(o) if o.success() => Ok(o),
+            Ok(o) => match self.limit_error(tool, pdf_file, args, &o) {
+                Some(e) => Err(e),
+                None if password::is_password_error(&o.stderr) => {
+                    Err(PdfError::password_required(pdf_file, &o.stderr).with_command(self.command(tool, args, Some(&o))))
+                },
+                None => Err(PdfError::new(error_kind, format!("{} failed with exit code {:?}: {:?}", tool, o.status, pdf_file), String::from_utf8_lossy(&o.stderr).to_string())
+                    .with_command(self.command(tool, args, Some(&o)))),
+            },
+            Err(e) => Err(PdfError::new(error_kind, format!("{} error: {:?}", tool, pdf_file), e.to_string())
+                .with_command(self.command(tool, args, None))
+                .with_source(e)),
+        }
+    }
+
+    //Like run_tool, answered from the result cache if one is set
+    //Only successful outputs are stored, a failing cache never fails the call
+    //Calls with a password bypass the cache: the password must not end up in a key,
+    //and a key without it would answer calls with a wrong password
+    fn run_tool_cached(&self, tool: &str, pdf_file: &Path, args: &[String]) -> io::Result<ToolOutput> {
+        let Some(cache) = self.cache.as_ref().filter(|_| !args.iter().any(|arg| is_password_flag(arg))) else {
+            return self.run_tool(tool, Some(pdf_file), None, args);
+        };
+        let Ok(content_hash) = ResultCache::content_hash(pdf_file) else {
+            return self.run_tool(tool, Some(pdf_file), None, args);
+        };
+
+        //the temporary config file is named per process, its content goes into the key instead
+        let file_arg = self.path_arg(pdf_file);
+        let config = self.config_file.as_ref().map(|file| (file.path().to_string_lossy(), file.contents()));
+        let normalized: Vec<String> = args.iter()
+            .map(|arg| match &config {
+                _ if *arg == file_arg => "<pdf>".to_string(),
+                Some((path, contents)) if path == arg => contents.to_string(),
+                _ => arg.clone(),
+            })
+            .collect();
+        let key = ResultCache::key(&content_hash, tool, &normalized, self.tool_version(tool));
+
+        if let Some(stdout) = cache.get(&key) {
+            return Ok(ToolOutput::ok(stdout));
+        }
+        let output = self.run_tool(tool, Some(pdf_file), None, args)?;
+        //a truncated output can exit 0, the limit is not part of the key
+        if output.success() && !output.truncated {
+            let _ = cache.put(&key, &output.stdout);
+        }
+        Ok(output)
+    }
+
+    /// The result cache, if one was set on the builder
+    pub fn cache(&self) -> Option<&ResultCache> {
+        self.cache.as_ref()
+    }
+
+    //A path argument, absolute in a sandbox as the tools run in an empty working directory
+    fn path_arg(&self, path: &Path) -> String {
+        match &self.sandbox {
+            Some(_) => std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().into_owned(),
+            None => path.to_string_lossy().into_owned(),
+        }
+    }
+
+    //Spawns the tool through the configured runner, traced with the tracing feature
+    fn run_tool(&self, tool: &str, pdf_file: Option<&Path>, files: Option<&OutputFiles>, args: &[String]) -> io::Result<ToolOutput> {
+        let limit = self.max_output.map(|(max_bytes, _)| OutputLimit { max_bytes, files: files.cloned() });
+        trace::run(self.runner.as_ref(), &self.tool_path(tool), pdf_file, args, limit.as_ref())
+    }
+
+    /// Path of the tool binary, the bare tool name in the tools folder or the .exe on windows
+    pub fn tool_path(&self, tool: &str) -> PathBuf {
+        self.tool_paths.get(tool).cloned().unwrap_or_else(|| self.tools_folder.join(tool))
+    }
+
+    /// Where each tool was found, only set for tools created by `XpdfTools::discover`
+    pub fn discovery(&self) -> Option<&DiscoveryReport> {
+        self.discovery.as_ref()
+    }
+
+    /// Why each tool candidate was accepted or rejected when the tools were built
+    pub fn tool_report(&self) -> &ToolReport {
+        &self.tool_report
+    }
+
+    /// Version reported by `<tool> -v` when the tools were built, None if the tool is missing or the banner could not be parsed
+    pub fn tool_version(&self, tool: &str) -> Option<&ToolVersion> {
+        self.tool_versions.get(tool)
+    }
+
+    /// The text encodings pdftotext knows, as listed by -listencodings
+    /// The list is kept per pdftotext path and xpdfrc for the lifetime of the process
+    pub fn available_encodings(&self) -> Result<Vec<String>, PdfError> {
+        self.pdf_check_tool("pdftotext")?;
+        let key = (self.tool_path("pdftotext"), self.config_file.as_ref().map(|file| file.contents().to_string()));
+        if let Some(encodings) = encoding::cached(&key) {
+            return Ok(encodings);
+        }
+
+        //only the xpdfrc matters for the list, other extra args could make the tool expect a pdf file
+        let backend = self.backend("pdftotext");
+        let config = self.extra_args.iter().flatten()
+            .find_map(|arg| match arg {
+                XpdfArgs::Config(path) => Some(path.clone()),
+                _ => None,
+            })
+            .or_else(|| self.config_file.as_ref().map(|file| file.path().to_path_buf()));
+        let mut args = vec![];
+        if let (Some(config), ToolBackend::Xpdf) = (config, backend) {
+            args.push("-cfg".to_string());
+            args.push(config.to_string_lossy().into_owned());
+        }
+        let list = XpdfArgs::Listencodings;
+        args.extend(list.to_str_for(backend));
+
+        let output = self.run_tool("pdftotext", None, None, &args).map_err(|e| {
+            PdfError::new(PdfErrorKind::PdfToTextError, format!("pdftotext {} error", list), e.to_string())
+                .with_command(self.command("pdftotext", &args, None))
+                .with_source(e)
+        })?;
+        let encodings = encoding::parse_encodings(&String::from_utf8_lossy(&output.stdout));
+        if encodings.is_empty() {
+            return Err(PdfError::new(PdfErrorKind::PdfToTextError, format!("pdftotext {} listed no encodings", list), String::from_utf8_lossy(&output.stderr).to_string())
+                .with_command(self.command("pdftotext", &args, Some(&output))));
+        }
+        encoding::store(key, encodings.clone());
+        Ok(encodings)
+    }
+
+    /// The implementation behind the tool, xpdf if the version could not be detected
+    pub fn backend(&self, tool: &str) -> ToolBackend {
+        self.tool_versions.get(tool).map(|v| v.backend).unwrap_or(ToolBackend::Xpdf)
+    }
+
+    //Runs the tool without extra passwords first and on a password error
+    //retries with every candidate of the password provider
+    fn with_passwords<T, F>(&self, pdf_file: &Path, mut run: F) -> Result<T, PdfError>
+    where
+        F: FnMut(Option<&PdfPassword>) -> Result<T, PdfError>,
+    {
+        trace::call(pdf_file, || {
+            let result = run(None);
+            let provider = match (&result, &self.password_provider) {
+                (Err(e), Some(provider)) if e.error_kind == PdfErrorKind::PasswordRequired => provider,
+                _ => return result,
+            };
+
+            let mut last = result;
+            for password in provider.passwords(pdf_file) {
+                last = run(Some(&password));
+                match &last {
+                    Err(e) if e.error_kind == PdfErrorKind::PasswordRequired => continue,
+                    _ => return last,
+                }
+            }
+            last
+        })
+    }
+
+    //Command line arguments for a tool invocation
+    //A given password replaces any password in the extra args
+    fn tool_args(&self, tool: &str, password: Option<&PdfPassword>) -> Result<Vec<String>, PdfError> {
+        self.check_args_supported(tool)?;
+        self.check_encoding(tool)?;
+
+        let backend = self.backend(tool);
+        let mut args = vec![];
+        if let Some(extra) = &self.extra_args {
+            match password {
+                Some(_) => {
+                    let without_pwd: Vec<_> = extra.iter()
+                        .filter(|arg| !matches!(arg, XpdfArgs::OwnerPassword(_) | XpdfArgs::UserPassword(_)))
+                        .cloned()
+                        .collect();
+                    args.extend(args_parser_for(&without_pwd, tool, backend));
+                },
+                None => args.extend(args_parser_for(extra, tool, backend)),
+            }
+        }
+        if let Some(password) = password {
+            args.extend(args_parser_for(&password.to_args(), tool, backend));
+        }
+        if let Some(file) = &self.config_file {
+            let explicit = self.extra_args.iter().flatten().any(|arg| matches!(arg, XpdfArgs::Config(_)));
+            let config = XpdfArgs::Config(file.path().into());
+            //pushed as two args, the path may contain spaces
+            if !explicit && config.is_valid_for(tool) && config.to_str_for(backend).is_some() {
+                args.push("-cfg".into());
+                args.push(file.path().to_string_lossy().into_owned());
+            }
+        }
+        Ok(args)
+    }
+
+    //Rejects extra args which the detected tool version does not know
+    fn check_args_supported(&self, tool: &str) -> Result<(), PdfError> {
+        let (Some(extra), Some(version)) = (&self.extra_args, self.tool_versions.get(tool)) else {
+            return Ok(());
+        };
+        match extra.iter().find(|arg| arg.is_valid_for(tool) && !version.supports(arg)) {
+            Some(arg) => Err(PdfError::new(PdfErrorKind::UnsupportedArgument, format!("{} is not supported by {} ({})", arg, tool, version), "".into())),
+            None => Ok(()),
+        }
+    }
+
+    //The encoding of the tool output: XpdfArgs::Encoding or the textEncoding of an XpdfConfig for the tools taking -enc,
+    //else what the tool writes by default, Latin1 for xpdf and UTF-8 for poppler
+    fn output_encoding(&self, tool: &str) -> String {
+        let backend = self.backend(tool);
+        let arg = self.extra_args.iter().flatten().find_map(|arg| match arg {
+            XpdfArgs::Encoding(name) => Some(name.clone()),
+            _ => None,
+        });
+        //the xpdfrc is only passed to xpdf
+        let configured = arg.or_else(|| {
+            let contents = self.config_file.as_ref().filter(|_| backend == ToolBackend::Xpdf)?.contents();
+            contents.lines().rev()
+                .find_map(|line| line.strip_prefix("textEncoding "))
+                .map(|value| value.trim().trim_matches('"').to_string())
+        });
+        match configured {
+            Some(name) if XpdfArgs::Encoding(name.clone()).is_valid_for(tool) => name,
+            _ => encoding::default_encoding(backend).to_string(),
         }
     }
+
+    //Rejects an XpdfArgs::Encoding which pdftotext does not list
+    //Skipped if the encodings can not be listed, e.g. by xpdf 3 or without pdftotext
+    fn check_encoding(&self, tool: &str) -> Result<(), PdfError> {
+        let Some(name) = self.extra_args.iter().flatten().find_map(|arg| match arg {
+            XpdfArgs::Encoding(name) if arg.is_valid_for(tool) => Some(name),
+            _ => None,
+        }) else {
+            return Ok(());
+        };
+        if self.pdf_check_tool("pdftotext").is_err() || self.tool_version("pdftotext").is_some_and(|v| !v.supports(&XpdfArgs::Listencodings)) {
+            return Ok(());
+        }
+        match self.available_encodings() {
+            Ok(available) => encoding::check_encoding(name, &available),
+            Err(_) => Ok(()),
+        }
+    }
+
+    //A ResourceLimitExceeded error if the failed tool ran into one of the limits
+    fn limit_error(&self, tool: &str, pdf_file: &Path, args: &[String], output: &ToolOutput) -> Option<PdfError> {
+        //a tool stopped at the output limit was killed by us
+        if output.success() || output.truncated {
+            return None;
+        }
+        let limit = self.limits.exceeded(output)?;
+        Some(PdfError::new(PdfErrorKind::ResourceLimitExceeded, format!("{} {}: {:?}", tool, limit, pdf_file), String::from_utf8_lossy(&output.stderr).to_string())
+            .with_command(self.command(tool, args, Some(output))))
+    }
+
+    //An OutputLimitExceeded error for a truncated output, None if truncated results are wanted
+    fn overflow_error(&self, tool: &str, pdf_file: &Path, args: &[String], output: &ToolOutput) -> Option<PdfError> {
+        match self.max_output {
+            Some((max_bytes, OutputOverflow::Fail)) if output.truncated => {
+                Some(PdfError::new(PdfErrorKind::OutputLimitExceeded, format!("{} output exceeded {} bytes: {:?}", tool, max_bytes, pdf_file), String::from_utf8_lossy(&output.stderr).to_string())
+                    .with_command(self.command(tool, args, Some(output))))
+            },
+            _ => None,
+        }
+    }
+
+    //The invocation for an error, passwords are redacted
+    fn command(&self, tool: &str, args: &[String], output: Option<&ToolOutput>) -> ToolCommand {
+        ToolCommand::new(tool, self.tool_path(tool), args, output)
+    }
+
+    fn tool_not_available(tool: &str) -> PdfError {
+        PdfError::new(PdfErrorKind::ToolNotAvailable, format!("{} not found in tools folder", tool), "".into())
+    }
 }
-#[derive(Debug)]
 pub struct XpdfToolsBuilder {
     tools_folder: PathBuf,
     extra_args: Option<Vec<XpdfArgs>>,
     available_tools: Option<Vec<String>>,
+    tool_paths: BTreeMap<String, PathBuf>,
+    tool_versions: BTreeMap<String, ToolVersion>,
+    tool_report: ToolReport,
+    discovery: Option<DiscoveryReport>,
+    password_provider: Option<Box<dyn PasswordProvider>>,
+    runner: Option<Arc<dyn ToolRunner>>,
+    cache: Option<ResultCache>,
+    config_file: Option<ConfigFile>,
+    limits: ResourceLimits,
+    sandbox: Option<SandboxPolicy>,
+    max_output: Option<(u64, OutputOverflow)>,
+}
+
+impl fmt::Debug for XpdfToolsBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XpdfToolsBuilder")
+            .field("tools_folder", &self.tools_folder)
+            .field("extra_args", &self.extra_args)
+            .field("available_tools", &self.available_tools)
+            .field("tool_paths", &self.tool_paths)
+            .field("tool_report", &self.tool_report)
+            .field("password_provider", &self.password_provider.is_some())
+            .field("cache", &self.cache)
+            .field("config_file", &self.config_file.as_ref().map(ConfigFile::path))
+            .field("limits", &self.limits)
+            .field("sandbox", &self.sandbox)
+            .field("max_output", &self.max_output)
+            .finish()
+    }
 }
 
 impl XpdfToolsBuilder {
@@ -108,23 +711,97 @@ impl XpdfToolsBuilder {
             let dir = tools_folder.read_dir().unwrap();
 
             //prop check https://github.com/rust-lang/libs-team/issues/311 for a better way to do this..
-            let valid_entries:Vec<_> =  dir
-                .filter_map(|entry| entry.ok())
-                .map(|entry| entry.path().file_stem().unwrap().to_owned())
-                .filter_map(|stem| stem.into_string().ok())
-                .filter(|s| VALID_TOOLS.contains(&s.as_str()))
+            //only files named exactly like a tool (pdfinfo, pdfinfo.exe on windows) are checked,
+            //pdfinfo.txt or pdfinfo.bak next to it are no candidates
+            let mut paths: Vec<PathBuf> = dir.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+            paths.sort();
+            let mut tool_report = ToolReport::default();
+            for path in &paths {
+                let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if let Some(tool) = VALID_TOOLS.iter().find(|tool| health::tool_file_names(tool).iter().any(|n| n == name)) {
+                    tool_report.push(tool, path, health::check_file(path));
+                }
+            }
+            let valid_entries: Vec<_> = tool_report.accepted().map(|check| check.tool.clone()).collect();
+            let tool_paths = tool_report.accepted()
+                .map(|check| (check.tool.clone(), check.path.clone()))
                 .collect();
             
             Ok(Self {
                 tools_folder,
                 extra_args: None,
                 available_tools: Some(valid_entries),
+                tool_paths,
+                tool_versions: BTreeMap::new(),
+                tool_report,
+                discovery: None,
+                password_provider: None,
+                runner: None,
+                cache: None,
+                config_file: None,
+                limits: ResourceLimits::default(),
+                sandbox: None,
+                max_output: None,
             })
         } else {
-            Err(PdfError { 
-                message: "Specified tools folder not found".into(), 
-                process_message: "".into(), 
-                error_kind: PdfErrorKind::NoXpdfToolsDirectory })
+            Err(PdfError::new(PdfErrorKind::NoXpdfToolsDirectory, "Specified tools folder not found".into(), "".into()))
+        }
+    }
+
+    fn from_discovery(report: DiscoveryReport) -> Result<Self, PdfError> {
+        //the tools folder is only a fallback, every found tool has its own path
+        let tools_folder = match report.found.values().next() {
+            Some(location) => location.path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            None => return Err(PdfError::new(PdfErrorKind::NoXpdfToolsDirectory, format!("No xpdf tools found in {}, PATH or common install prefixes", discover::TOOLS_DIR_ENV), "".into())),
+        };
+
+        let mut tool_report = ToolReport::default();
+        for (tool, location) in &report.found {
+            tool_report.push(tool, &location.path, health::check_file(&location.path));
+        }
+        let accepted: Vec<String> = tool_report.accepted().map(|check| check.tool.clone()).collect();
+
+        Ok(Self {
+            tools_folder,
+            extra_args: None,
+            tool_paths: accepted.iter().map(|tool| (tool.clone(), report.found[tool].path.clone())).collect(),
+            available_tools: Some(accepted),
+            tool_versions: BTreeMap::new(),
+            tool_report,
+            discovery: Some(report),
+            password_provider: None,
+            runner: None,
+            cache: None,
+            config_file: None,
+            limits: ResourceLimits::default(),
+            sandbox: None,
+            max_output: None,
+        })
+    }
+
+    fn unchecked(tools_folder: PathBuf, tools: &[&str]) -> Self {
+        let mut tool_report = ToolReport::default();
+        for tool in tools {
+            tool_report.push(tool, &tools_folder.join(tool), ToolStatus::Accepted);
+        }
+
+        Self {
+            tool_paths: tools.iter().map(|tool| (tool.to_string(), tools_folder.join(tool))).collect(),
+            available_tools: Some(tools.iter().map(|tool| tool.to_string()).collect()),
+            tools_folder,
+            extra_args: None,
+            tool_versions: BTreeMap::new(),
+            tool_report,
+            discovery: None,
+            password_provider: None,
+            runner: None,
+            cache: None,
+            config_file: None,
+            limits: ResourceLimits::default(),
+            sandbox: None,
+            max_output: None,
         }
     }
 
@@ -136,13 +813,125 @@ impl XpdfToolsBuilder {
         self
     }
 
-    pub fn build(self) -> XpdfTools {
-        XpdfTools { extra_args: self.extra_args, tools_folder: self.tools_folder, available_tools: self.available_tools}
+    //Source of candidate passwords, asked when a tool reports an incorrect password
+    pub fn password_provider(mut self, provider: impl PasswordProvider + 'static) -> Self {
+        self.password_provider = Some(Box::new(provider));
+        self
+    }
+
+    //Runs the tool processes, the default spawns child processes
+    pub fn runner(mut self, runner: impl ToolRunner + 'static) -> Self {
+        self.runner = Some(Arc::new(runner));
+        self
+    }
+
+    //Limits for every tool process, set by the default runner with setrlimit on unix
+    //A custom runner has to apply them itself, breaches are still reported as ResourceLimitExceeded
+    pub fn resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    //Runs every tool process in an isolated environment for untrusted pdf files, see SandboxPolicy
+    //Relative pdf and output paths are made absolute, the tools run in an empty working directory
+    //Like the limits it is applied by the default runner
+    pub fn sandbox(mut self, policy: SandboxPolicy) -> Self {
+        self.sandbox = Some(policy);
+        self
+    }
+
+    //The most a tool invocation may print or write into its output files, against decompression bombs
+    //The tool is killed at the limit, the call fails or returns what was written up to it
+    //A custom runner has to implement run_limited to stop the tool early
+    pub fn max_output(mut self, max_bytes: u64, overflow: OutputOverflow) -> Self {
+        self.max_output = Some((max_bytes, overflow));
+        self
+    }
+
+    fn tool_runner(&self) -> Arc<dyn ToolRunner> {
+        match (&self.runner, &self.sandbox) {
+            (Some(runner), _) => runner.clone(),
+            (None, Some(policy)) => Arc::new(ProcessRunner::new().limits(self.limits).sandbox(policy.clone())),
+            (None, None) => Arc::new(ProcessRunner::new().limits(self.limits)),
+        }
+    }
+
+    //Caches the outputs of the tools which only print results (info, text, fonts, image lists...)
+    pub fn cache(mut self, cache: ResultCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    //Writes the config to a temporary xpdfrc file passed as -cfg to every xpdf tool
+    //An XpdfArgs::Config in the extra args takes precedence, poppler tools have no -cfg
+    pub fn config(mut self, config: XpdfConfig) -> Result<Self, PdfError> {
+        let file = config.write_temp().map_err(|e| {
+            PdfError::new(PdfErrorKind::ConfigError, "Could not write the xpdfrc file".into(), e.to_string()).with_source(e)
+        })?;
+        self.config_file = Some(file);
+        Ok(self)
+    }
+
+    //Runs every available tool with -v now, tools without a version banner are rejected
+    pub fn smoke_test(mut self) -> Self {
+        let tools: Vec<String> = self.tool_paths.keys().cloned().collect();
+        let runner = self.tool_runner();
+        for tool in tools {
+            match ToolVersion::detect_with(runner.as_ref(), &self.tool_paths[&tool]) {
+                Some(version) => { self.tool_versions.insert(tool, version); },
+                None => {
+                    self.tool_report.reject(&tool, ToolStatus::SmokeTestFailed);
+                    self.tool_paths.remove(&tool);
+                    if let Some(available) = self.available_tools.as_mut() {
+                        available.retain(|t| *t != tool);
+                    }
+                },
+            }
+        }
+        self
+    }
+
+    pub fn tool_report(&self) -> &ToolReport {
+        &self.tool_report
     }
 
-    pub fn args_parser<'a>(args: &'a Vec<XpdfArgs>) -> Box<dyn Iterator<Item = String> + 'a> {
+    //Runs every available tool with -v to detect its version
+    //Fails if a tool candidate was rejected or no tool is usable, a broken install is found at startup
+    pub fn build(mut self) -> Result<XpdfTools, PdfError> {
+        if !self.tool_report.is_healthy() {
+            return Err(PdfError::new(PdfErrorKind::ToolNotUsable, format!("Xpdf tools are not usable in {}", self.tools_folder.display()), self.tool_report.to_string()));
+        }
+
+        let runner = self.tool_runner();
+        for (tool, path) in &self.tool_paths {
+            if !self.tool_versions.contains_key(tool) {
+                if let Some(version) = ToolVersion::detect_with(runner.as_ref(), path) {
+                    self.tool_versions.insert(tool.clone(), version);
+                }
+            }
+        }
+
+        Ok(XpdfTools {
+            extra_args: self.extra_args,
+            tools_folder: self.tools_folder,
+            available_tools: self.available_tools,
+            tool_paths: self.tool_paths,
+            tool_versions: self.tool_versions,
+            tool_report: self.tool_report,
+            discovery: self.discovery,
+            password_provider: self.password_provider,
+            runner,
+            cache: self.cache,
+            config_file: self.config_file,
+            limits: self.limits,
+            sandbox: self.sandbox,
+            max_output: self.max_output,
+        })
+    }
+
+    pub fn args_parser<'a>(args: &'a [XpdfArgs]) -> Box<dyn Iterator<Item = String> + 'a> {
         Box::new(
-            args.into_iter()
+            args.iter()
             //.filter(|xpdfarg| xpdfarg.is_valid_totext_arg())
             .map(|xpdfarg| xpdfarg.to_str())
             .flat_map(|str| {
@@ -152,15 +941,141 @@ impl XpdfToolsBuilder {
     }
 }
 
-pub fn args_parser<'a>(args: &'a Vec<XpdfArgs>, tool: &'a str) -> Box<dyn Iterator<Item = String> + 'a> {
+//Output root for tools writing numbered files, <out_dir>/<pdf file stem>
+fn output_root(pdf_file: &Path, out_dir: &Path) -> PathBuf {
+    out_dir.join(pdf_file.file_stem().unwrap_or_default())
+}
+
+//Files named <root>-<number>.<ext>, as written by pdfimages and the renderers, with their modification time
+//"a-b-000001.png" of another document does not match the root "a"
+fn files_with_root(root: &Path) -> BTreeMap<PathBuf, Option<SystemTime>> {
+    let (Some(dir), Some(name)) = (root.parent(), root.file_name()) else {
+        return BTreeMap::new();
+    };
+    let prefix = format!("{}-", name.to_string_lossy());
+    let numbered = |path: &Path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let number = name.strip_prefix(&prefix).and_then(|rest| rest.split('.').next()).unwrap_or_default();
+        !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())
+    };
+    match dir.read_dir() {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| numbered(path))
+            .map(|path| {
+                let modified = path.metadata().and_then(|metadata| metadata.modified()).ok();
+                (path, modified)
+            })
+            .collect(),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+//The files with the root written by a run: new ones and those changed since the listing before it
+//Files left by earlier runs are not returned
+fn written_files(root: &Path, before: &BTreeMap<PathBuf, Option<SystemTime>>) -> Vec<PathBuf> {
+    files_with_root(root).into_iter()
+        .filter(|(path, modified)| before.get(path) != Some(modified))
+        .map(|(path, _)| path)
+        .collect()
+}
+
+pub fn args_parser<'a>(args: &'a [XpdfArgs], tool: &'a str) -> Box<dyn Iterator<Item = String> + 'a> {
+    args_parser_for(args, tool, ToolBackend::Xpdf)
+}
+
+//Like args_parser, translating the args for the backend and dropping those without an equivalent
+pub fn args_parser_for<'a>(args: &'a [XpdfArgs], tool: &'a str, backend: ToolBackend) -> Box<dyn Iterator<Item = String> + 'a> {
     Box::new(
-        args.into_iter()
+        args.iter()
         .filter(|xpdfarg| xpdfarg.is_valid_for(tool))
-        .map(|xpdfarg| xpdfarg.to_str())
-        .flat_map(|str| str.split(" ").map(|str| str.to_owned()).collect::<Vec<_>>())
+        .filter_map(move |xpdfarg| xpdfarg.to_args_for(backend))
+        .flatten()
     )
 }
 
+#[test]
+fn test_fake_runner_tool_args() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdfimages", ToolOutput::ok(""))
+        .with_output("pdftoppm", ToolOutput::ok("pdftoppm version 22.02.0\nCopyright 2005-2022 The Poppler Developers - http://poppler.freedesktop.org\n"))
+        .with_output("pdftoppm", ToolOutput::ok(""))
+        .with_output("pdffonts", ToolOutput::failed(1, "Syntax Error: Couldn't read xref table\n"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdfimages", "pdftoppm", "pdffonts"])
+        .runner(runner.clone())
+        .extra_args(vec![XpdfArgs::FirstPage(2), XpdfArgs::LastPage(3), XpdfArgs::RawDates])
+        .build().unwrap();
+
+    assert!(tools.pdf_images(Path::new("a.pdf")).unwrap().images.is_empty());
+    tools.pdf_render(Path::new("a.pdf"), Path::new("/tmp/out"), 150).unwrap();
+    assert_eq!(tools.pdf_fonts(Path::new("a.pdf")).unwrap_err().kind(), PdfErrorKind::PdfFontsError);
+
+    let args: Vec<_> = runner.invocations().into_iter().filter(|i| i.args != ["-v"]).map(|i| i.args).collect();
+    assert_eq!(args[0], vec!["-f", "2", "-l", "3", "-list", "a.pdf"]);
+    assert_eq!(args[1], vec!["-f", "2", "-l", "3", "-png", "-r", "150", "a.pdf", "/tmp/out/a"]);
+}
+
+#[test]
+fn test_fake_runner_cache() {
+    use runner::FakeRunner;
+
+    let root = std::env::temp_dir().join(format!("xpdf_tools_cache_{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let (first, copy) = (root.join("first.pdf"), root.join("copy.pdf"));
+    std::fs::write(&first, "%PDF-1.4 same content").unwrap();
+    std::fs::write(&copy, "%PDF-1.4 same content").unwrap();
+
+    let runner = FakeRunner::new().with_output("pdftotext", ToolOutput::ok("cached text"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdftotext"])
+        .runner(runner.clone())
+        .cache(ResultCache::new(root.join("cache"), 1024 * 1024).unwrap())
+        .build().unwrap();
+
+    assert_eq!(tools.pdf_text_as_string(&first).unwrap(), "cached text");
+    assert_eq!(tools.pdf_text_as_string(&copy).unwrap(), "cached text");
+    let text_calls = || runner.invocations().iter().filter(|i| i.args != ["-v"]).count();
+    assert_eq!(text_calls(), 1);
+
+    tools.cache().unwrap().invalidate(&first).unwrap();
+    tools.pdf_text_as_string(&copy).unwrap();
+    assert_eq!(text_calls(), 2);
+
+    //calls with a password are neither answered from nor stored in the cache
+    let entries = tools.cache().unwrap().size().unwrap();
+    let with_password = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdftotext"])
+        .runner(runner.clone())
+        .extra_args(vec![XpdfArgs::OwnerPassword("secret".into())])
+        .cache(tools.cache().unwrap().clone())
+        .build().unwrap();
+    with_password.pdf_text_as_string(&first).unwrap();
+    with_password.pdf_text_as_string(&first).unwrap();
+    assert_eq!(text_calls(), 4);
+    assert_eq!(tools.cache().unwrap().size().unwrap(), entries);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(test)]
+mod fixtures {
+    use std::path::PathBuf;
+
+    pub fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures").join(name)
+    }
+
+    //The sample outputs of a tool, one file per output layout
+    pub fn layouts(tool: &str) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fixture(tool).read_dir().unwrap()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect();
+        files.sort();
+        files
+    }
+}
+
 pub fn get_version() -> String {
     format!("XpdfTools version: {}",env!("CARGO_PKG_VERSION"))
 }
@@ -178,7 +1093,7 @@ fn test_builder_errors() {
 
 #[test]
 fn test_tool_available() {
-    let tools_result = XpdfTools::builder(PathBuf::from("./testData/binTester")).unwrap().build();
+    let tools_result = XpdfTools::builder(PathBuf::from("./testData/binTester")).unwrap().build().unwrap();
     assert_eq!(tools_result.pdf_check_tool("pdfinfo"), Ok(true));
     assert_eq!(tools_result.pdf_check_tool("pdftotext"), Ok(true));
     assert_eq!(tools_result.pdf_check_tool("pdftoWindmill").unwrap_err().kind(), PdfErrorKind::ToolNotAvailable);
@@ -188,7 +1103,7 @@ fn test_tool_available() {
 fn test_arguments() {
     let tools_result = XpdfTools::builder(PathBuf::from("./testData/binTester")).unwrap()
         .extra_args(vec![XpdfArgs::RawDates, XpdfArgs::Metadata])
-        .build();
+        .build().unwrap();
     let result = tools_result.pdf_info(Path::new("./testData/pdfFile_01.pdf"));
 
     assert!(result.as_ref().unwrap().info_map.get("Metadata").is_some());
@@ -201,7 +1116,7 @@ fn test_pdf_text() {
     let tools_result = XpdfTools::builder(PathBuf::from("./testData/binTester")).unwrap()
         .extra_args(vec![XpdfArgs::Encoding("UTF-8".into())])
         //.extra_args(vec![XpdfArgs::FirstPage(2)])
-        .build();
+        .build().unwrap();
 
     let pdf_text = tools_result.pdf_text(Path::new("./testData/sample_text.pdf"));
     //let pdf_text = tools_result.pdf_text(Path::new("./testData/descartes_meditations.pdf"));
@@ -218,9 +1133,506 @@ fn test_args_parser() {
     let tools_result = XpdfTools::builder(PathBuf::from("./testData/binTester")).unwrap()
     //.extra_args(vec![XpdfArgs::Encoding("UTF-8".into())])
     .extra_args(vec![XpdfArgs::FirstPage(2), XpdfArgs::Metadata, XpdfArgs::Encoding("UTF-8".into()), XpdfArgs::RawDates])
-    .build();
+    .build().unwrap();
 
     let args = tools_result.extra_args.unwrap();
     let args_strings:Vec<_> = XpdfToolsBuilder::args_parser(&args).collect();
     println!("args_string: {:?}", args_strings);
-}
\ No newline at end of file
+}
+#[test]
+fn test_fake_runner_pdf_info() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdfinfo", ToolOutput::ok("pdfinfo version 4.04\nCopyright 1996-2022 Glyph & Cog, LLC\n"))
+        .with_output("pdfinfo", ToolOutput::ok("Title:          Report\nPages:          3\n"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdfinfo"])
+        .runner(runner.clone())
+        .extra_args(vec![XpdfArgs::RawDates, XpdfArgs::Layout])
+        .build().unwrap();
+
+    let info = tools.pdf_info(Path::new("report.pdf")).unwrap();
+    assert_eq!(info.info_map["Pages"], Some("3".into()));
+    assert_eq!(tools.tool_version("pdfinfo").unwrap().version, "4.04");
+
+    let invocations = runner.invocations();
+    assert_eq!(invocations[1].program, PathBuf::from("/opt/xpdf/pdfinfo"));
+    assert_eq!(invocations[1].args, vec!["-rawdates".to_string(), "report.pdf".into()]);
+}
+
+#[test]
+fn test_fake_runner_password_retry() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdftotext", ToolOutput::ok("pdftotext version 4.04\n"))
+        .with_output("pdftotext", ToolOutput::failed(1, "Command Line Error: Incorrect password\n"))
+        .with_output("pdftotext", ToolOutput::failed(1, "Command Line Error: Incorrect password\n"))
+        .with_output("pdftotext", ToolOutput::ok("secret text"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdftotext"])
+        .runner(runner.clone())
+        .password_provider(|_: &Path| vec![PdfPassword::user("wrong"), PdfPassword::user("right")])
+        .build().unwrap();
+
+    assert_eq!(tools.pdf_text_as_string(Path::new("locked.pdf")).unwrap(), "secret text");
+    let args: Vec<_> = runner.invocations().into_iter().map(|i| i.args).collect();
+    assert_eq!(args.last().unwrap(), &vec!["-upw".to_string(), "right".into(), "locked.pdf".into(), "-".into()]);
+
+    let no_provider = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdftotext"])
+        .runner(FakeRunner::new().with_output("pdftotext", ToolOutput::failed(1, "Command Line Error: Incorrect password\n")))
+        .build().unwrap();
+    assert_eq!(no_provider.pdf_text(Path::new("locked.pdf")).unwrap_err().kind(), PdfErrorKind::PasswordRequired);
+}
+
+#[test]
+fn test_fake_runner_unsupported_argument() {
+    use runner::FakeRunner;
+
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdftotext"])
+        .runner(FakeRunner::new().with_output("pdftotext", ToolOutput::ok("pdftotext version 3.04\n")))
+        .extra_args(vec![XpdfArgs::Table])
+        .build().unwrap();
+
+    let err = tools.pdf_text(Path::new("prices.pdf")).unwrap_err();
+    assert_eq!(err.kind(), PdfErrorKind::UnsupportedArgument);
+    assert_eq!(err.message, "-table is not supported by pdftotext (xpdf 3.04)");
+}
+
+#[test]
+fn test_fake_runner_grep() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdftotext", ToolOutput::ok("pdftotext version 4.04\n"))
+        .with_output("pdftotext", ToolOutput::ok("Invoice\nPayment within 30 days\n\x0cLate payment fee\n\x0c"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdftotext"])
+        .runner(runner)
+        .build().unwrap();
+
+    let regex = Regex::new("payment").unwrap();
+    let results = tools.grep(&["a.pdf", "b.pdf"], &regex, GrepOptions { ignore_case: true, ..Default::default() }).unwrap();
+    let found: Vec<_> = results.matches.iter().map(|m| (m.path.to_str().unwrap(), m.page, m.line)).collect();
+    assert_eq!(found, vec![("a.pdf", 1, 2), ("a.pdf", 2, 1), ("b.pdf", 1, 2), ("b.pdf", 2, 1)]);
+    assert!(results.errors.is_empty());
+}
+
+#[test]
+fn test_fake_runner_grep_broken_file() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdftotext", ToolOutput::ok("pdftotext version 4.04\n"))
+        .with_output("pdftotext", ToolOutput::ok("Payment within 30 days\n\x0c"))
+        .with_output("pdftotext", ToolOutput::failed(1, "Syntax Error: Couldn't find trailer dictionary\n"))
+        .with_output("pdftotext", ToolOutput::ok("Late payment fee\n\x0c"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdftotext"])
+        .runner(runner)
+        .build().unwrap();
+
+    let regex = Regex::new("(?i)payment").unwrap();
+    let results = tools.grep(&["a.pdf", "broken.pdf", "c.pdf"], &regex, GrepOptions::default()).unwrap();
+    let found: Vec<_> = results.matches.iter().map(|m| m.path.to_str().unwrap()).collect();
+    assert_eq!(found, vec!["a.pdf", "c.pdf"]);
+    assert_eq!(results.errors.len(), 1);
+    assert_eq!(results.errors[0].0, PathBuf::from("broken.pdf"));
+    assert_eq!(results.errors[0].1.kind(), PdfErrorKind::PdfToTextErrorNoOutput);
+}
+
+#[test]
+fn test_fake_runner_tables() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdftotext", ToolOutput::ok("pdftotext version 4.04\n"))
+        .with_output("pdftotext", ToolOutput::ok("Item      Price\nApple      0.50\n\x0c"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdftotext"])
+        .runner(runner.clone())
+        .build().unwrap();
+
+    let tables = tools.pdf_tables(Path::new("prices.pdf")).unwrap();
+    assert_eq!(tables[0].rows, vec![vec!["Item", "Price"], vec!["Apple", "0.50"]]);
+    assert_eq!(runner.invocations()[1].args, vec!["-layout".to_string(), "prices.pdf".into(), "-".into()]);
+}
+
+#[test]
+fn test_fake_runner_config() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdftotext", ToolOutput::ok("pdftotext version 4.04\n"))
+        .with_output("pdftotext", ToolOutput::ok("text\x0c"))
+        .with_output("pdftoppm", ToolOutput::ok("pdftoppm version 22.02.0\nCopyright 2005-2022 The Poppler Developers - http://poppler.freedesktop.org\n"))
+        .with_output("pdftoppm", ToolOutput::ok(""));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf"), &["pdftotext", "pdftoppm"])
+        .runner(runner.clone())
+        .config(XpdfConfig::new().text_encoding("UTF-8")).unwrap()
+        .build().unwrap();
+    let config_file = tools.config_file.as_ref().unwrap().path().to_path_buf();
+    assert_eq!(std::fs::read_to_string(&config_file).unwrap(), "textEncoding UTF-8\n");
+
+    tools.pdf_text(Path::new("a.pdf")).unwrap();
+    tools.pdf_render(Path::new("a.pdf"), Path::new("/tmp/out"), 150).unwrap();
+    let invocations = runner.invocations();
+    let text = invocations.iter().find(|i| i.tool() == "pdftotext" && i.args.len() > 1).unwrap();
+    assert_eq!(text.args, vec!["-cfg".to_string(), config_file.to_string_lossy().into_owned(), "a.pdf".into(), "-".into()]);
+    let render = invocations.iter().find(|i| i.tool() == "pdftoppm" && i.args.len() > 1).unwrap();
+    assert!(!render.args.contains(&"-cfg".to_string()));
+
+    drop(tools);
+    assert!(!config_file.exists());
+}
+
+#[test]
+fn test_fake_runner_encodings() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdftotext", ToolOutput::ok("pdftotext version 4.04\n"))
+        .with_output("pdftotext", ToolOutput::ok("Available encodings:\n  Latin1\n  UTF-8\n"))
+        .with_output("pdftotext", ToolOutput::ok("text\x0c"));
+    let folder = PathBuf::from("/opt/xpdf-encodings");
+    let tools = XpdfTools::builder_unchecked(folder.clone(), &["pdftotext"])
+        .runner(runner.clone())
+        .extra_args(vec![XpdfArgs::Encoding("UTF8".into())])
+        .build().unwrap();
+
+    let error = tools.pdf_text(Path::new("a.pdf")).unwrap_err();
+    assert_eq!(error.kind(), PdfErrorKind::UnsupportedArgument);
+    assert_eq!(error.message, "Unknown encoding UTF8, did you mean UTF-8?");
+    assert_eq!(runner.invocations()[1].args, vec!["-listencodings".to_string()]);
+    assert_eq!(runner.invocations().len(), 2);
+
+    //the list is cached for the tools folder
+    let other = XpdfTools::builder_unchecked(folder, &["pdftotext"])
+        .runner(runner.clone())
+        .extra_args(vec![XpdfArgs::Encoding("UTF-8".into())])
+        .build().unwrap();
+    other.pdf_text(Path::new("a.pdf")).unwrap();
+    assert_eq!(other.available_encodings().unwrap(), vec!["Latin1", "UTF-8"]);
+    assert_eq!(runner.invocations().last().unwrap().args, vec!["-enc".to_string(), "UTF-8".into(), "a.pdf".into(), "-".into()]);
+}
+
+#[test]
+fn test_fake_runner_decoding() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdftotext", ToolOutput::ok("pdftotext version 4.04\n"))
+        .with_output("pdftotext", ToolOutput::ok("Available encodings:\n  Latin1\n  UTF-8\n"))
+        .with_output("pdftotext", ToolOutput::ok(&b"Gr\xfc\xdfe\x0c"[..]))
+        .with_output("pdftotext", ToolOutput::ok(&b"Gr\xfc\xdfe\x0c"[..]))
+        .with_output("pdftotext", ToolOutput::ok(&b"Stra\xdfe    Nr\n\xc4hre      12\n\x0c"[..]))
+        .with_output("pdfdetach", ToolOutput::ok("pdfdetach version 4.04\n"))
+        .with_output("pdfdetach", ToolOutput::ok(&b"1 embedded files\n1: M\xfcller.txt\n"[..]));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-decoding"), &["pdftotext", "pdfdetach"])
+        .runner(runner)
+        .extra_args(vec![XpdfArgs::Encoding("Latin1".into())])
+        .build().unwrap();
+
+    assert_eq!(tools.pdf_text_pages(Path::new("a.pdf")).unwrap().pages, vec!["Grüße"]);
+    assert_eq!(tools.pdf_text(Path::new("a.pdf")).unwrap(), b"Gr\xfc\xdfe\x0c");
+    assert_eq!(tools.pdf_tables(Path::new("a.pdf")).unwrap()[0].rows, vec![vec!["Straße", "Nr"], vec!["Ähre", "12"]]);
+    assert_eq!(tools.pdf_attachments(Path::new("a.pdf")).unwrap()[0].name, "Müller.txt");
+}
+
+#[test]
+fn test_fake_runner_default_encoding() {
+    use runner::FakeRunner;
+
+    //without -enc xpdf writes Latin1 and poppler UTF-8
+    let runner = FakeRunner::new()
+        .with_output("pdfinfo", ToolOutput::ok("pdfinfo version 4.04\n"))
+        .with_output("pdfinfo", ToolOutput::ok(&b"Author:         M\xfcller\nPages:          1\n"[..]))
+        .with_output("pdffonts", ToolOutput::ok("pdffonts version 22.02.0\nCopyright 2005-2022 The Poppler Developers - http://poppler.freedesktop.org\n"))
+        .with_output("pdffonts", ToolOutput::ok(concat!(
+            "name                                 type              encoding         emb sub uni object ID\n",
+            "------------------------------------ ----------------- ---------------- --- --- --- ---------\n",
+            "Grüße                                TrueType          WinAnsi          yes yes yes      9  0\n")));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-default-encoding"), &["pdfinfo", "pdffonts"])
+        .runner(runner)
+        .build().unwrap();
+
+    assert_eq!(tools.pdf_info(Path::new("a.pdf")).unwrap().info_map["Author"], Some("Müller".into()));
+    assert_eq!(tools.pdf_fonts(Path::new("a.pdf")).unwrap().fonts[0].name, "Grüße");
+
+    //an undecodable byte in the XMP metadata is replaced instead of failing the call
+    let runner = FakeRunner::new()
+        .with_output("pdfinfo", ToolOutput::ok("pdfinfo version 4.04\n"))
+        .with_output("pdfinfo", ToolOutput::ok(&b"Pages:          1\nMetadata:\n<dc:creator>M\xfcller</dc:creator>\n"[..]));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-default-encoding"), &["pdfinfo"])
+        .runner(runner)
+        .extra_args(vec![XpdfArgs::Encoding("ASCII7".into()), XpdfArgs::Metadata])
+        .build().unwrap();
+
+    let info = tools.pdf_info(Path::new("a.pdf")).unwrap();
+    assert_eq!(info.info_map["Pages"], Some("1".into()));
+    assert!(info.raw.contains("M\u{fffd}ller"));
+}
+
+#[test]
+fn test_password_with_space() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdffonts", ToolOutput::ok("pdffonts version 4.04\n"))
+        .with_output("pdffonts", ToolOutput::failed(3, "Syntax Error: Couldn't read xref table\n"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-space"), &["pdffonts"])
+        .runner(runner.clone())
+        .extra_args(vec![XpdfArgs::UserPassword("my secret pass".into()), XpdfArgs::Encoding("Latin 1".into())])
+        .build().unwrap();
+
+    let error = tools.pdf_fonts(Path::new("a.pdf")).unwrap_err();
+    assert_eq!(runner.invocations()[1].args, vec!["-upw".to_string(), "my secret pass".into(), "a.pdf".into()]);
+    let command = error.command().unwrap();
+    assert_eq!(command.args, vec!["-upw".to_string(), "***".into(), "a.pdf".into()]);
+    assert!(!command.to_string().contains("secret") && !command.to_string().contains("pass"));
+    assert!(!format!("{:?}", error).contains("secret"));
+    assert_eq!(XpdfArgs::Encoding("Latin 1".into()).to_args_for(ToolBackend::Xpdf).unwrap(), vec!["-enc", "Latin 1"]);
+}
+
+#[test]
+fn test_error_command() {
+    use runner::FakeRunner;
+    use std::error::Error;
+
+    let runner = FakeRunner::new()
+        .with_output("pdffonts", ToolOutput::ok("pdffonts version 4.04\n"))
+        .with_output("pdffonts", ToolOutput::failed(1, "Command Line Error: Incorrect password\n"))
+        .with_output("pdffonts", ToolOutput::failed(3, "Syntax Error: Couldn't read xref table\nSyntax Error: Invalid XRef entry\n"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-errors"), &["pdffonts"])
+        .runner(runner)
+        .password_provider(|_: &Path| vec![PdfPassword::owner("hunter2")])
+        .build().unwrap();
+
+    let error = tools.pdf_fonts(Path::new("broken.pdf")).unwrap_err();
+    assert_eq!(error.kind(), PdfErrorKind::PdfFontsError);
+    let command = error.command().unwrap();
+    assert_eq!(command.tool, "pdffonts");
+    assert_eq!(command.args, vec!["-opw".to_string(), "***".into(), "broken.pdf".into()]);
+    assert_eq!(command.status, Some(3));
+    assert_eq!(command.stderr, "Syntax Error: Couldn't read xref table\nSyntax Error: Invalid XRef entry\n");
+    assert_eq!(command.to_string(), "/opt/xpdf-errors/pdffonts -opw *** broken.pdf (exit status 3)");
+    assert!(error.source().is_none());
+
+    //the io error of a process which could not be started is the source
+    struct MissingRunner;
+    impl ToolRunner for MissingRunner {
+        fn run(&self, _program: &Path, _args: &[String]) -> io::Result<ToolOutput> {
+            Err(io::Error::new(io::ErrorKind::PermissionDenied, "permission denied"))
+        }
+    }
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-errors"), &["pdffonts"]).runner(MissingRunner).build().unwrap();
+    let error = tools.pdf_fonts(Path::new("a.pdf")).unwrap_err();
+    let source = error.source().unwrap().downcast_ref::<io::Error>().unwrap();
+    assert_eq!(source.kind(), io::ErrorKind::PermissionDenied);
+    assert_eq!(error.command().unwrap().status, None);
+}
+
+#[test]
+fn test_resource_limit_exceeded() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdftotext", ToolOutput::ok("pdftotext version 4.04\n"))
+        .with_output("pdftotext", ToolOutput::failed(99, "Syntax Warning: ...\nOut of memory\n"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-limits"), &["pdftotext"])
+        .runner(runner.clone())
+        .resource_limits(limits::ResourceLimits::new().address_space(256 << 20))
+        .build().unwrap();
+
+    let error = tools.pdf_text(Path::new("bomb.pdf")).unwrap_err();
+    assert_eq!(error.kind(), PdfErrorKind::ResourceLimitExceeded);
+    assert_eq!(error.message, "pdftotext address space limit of 268435456 bytes exceeded: \"bomb.pdf\"");
+
+    //without limits the same failure is a plain tool error
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-limits"), &["pdftotext"]).runner(runner).build().unwrap();
+    assert_eq!(tools.pdf_text(Path::new("bomb.pdf")).unwrap_err().kind(), PdfErrorKind::PdfToTextErrorNoOutput);
+}
+
+#[test]
+fn test_sandbox_absolute_paths() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new().with_output("pdfinfo", ToolOutput::ok("Pages: 1\n"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-sandbox"), &["pdfinfo"])
+        .runner(runner.clone())
+        .sandbox(SandboxPolicy::new())
+        .build().unwrap();
+    tools.pdf_info(Path::new("docs/a.pdf")).unwrap();
+
+    let args = runner.invocations().pop().unwrap().args;
+    assert_eq!(args, vec![std::env::current_dir().unwrap().join("docs/a.pdf").to_string_lossy().into_owned()]);
+}
+
+#[test]
+fn test_output_limit() {
+    use runner::FakeRunner;
+
+    let runner = FakeRunner::new()
+        .with_output("pdftotext", ToolOutput::ok("pdftotext version 4.04\n"))
+        .with_output("pdftotext", ToolOutput::ok("bomb ".repeat(1000)));
+    let builder = |overflow| XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-output"), &["pdftotext"])
+        .runner(runner.clone())
+        .max_output(20, overflow)
+        .build().unwrap();
+
+    let error = builder(OutputOverflow::Fail).pdf_text(Path::new("bomb.pdf")).unwrap_err();
+    assert_eq!(error.kind(), PdfErrorKind::OutputLimitExceeded);
+    assert_eq!(error.message, "pdftotext output exceeded 20 bytes: \"bomb.pdf\"");
+    assert_eq!(builder(OutputOverflow::Truncate).pdf_text(Path::new("bomb.pdf")).unwrap(), b"bomb bomb bomb bomb ");
+}
+
+#[test]
+fn test_output_limit_not_cached() {
+    use runner::FakeRunner;
+
+    let root = std::env::temp_dir().join(format!("xpdf_tools_output_cache_{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let pdf = root.join("bomb.pdf");
+    std::fs::write(&pdf, "%PDF-1.4 bomb").unwrap();
+
+    //a runner per call, so both detect the same version and use the same cache key
+    let runner = || FakeRunner::new()
+        .with_output("pdftotext", ToolOutput::ok("pdftotext version 4.04\n"))
+        .with_output("pdftotext", ToolOutput::ok("bomb ".repeat(1000)));
+    let cache = ResultCache::new(root.join("cache"), 1024 * 1024).unwrap();
+    let builder = |overflow| XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-output-cache"), &["pdftotext"])
+        .runner(runner())
+        .max_output(20, overflow)
+        .cache(cache.clone())
+        .build().unwrap();
+
+    assert_eq!(builder(OutputOverflow::Truncate).pdf_text(&pdf).unwrap(), b"bomb bomb bomb bomb ");
+    let error = builder(OutputOverflow::Fail).pdf_text(&pdf).unwrap_err();
+    std::fs::remove_dir_all(&root).unwrap();
+    assert_eq!(error.kind(), PdfErrorKind::OutputLimitExceeded);
+}
+
+#[test]
+fn test_passwords_redacted() {
+    let builder = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-redacted"), &["pdfinfo"])
+        .extra_args(vec![XpdfArgs::OwnerPassword("hunter2".into()), XpdfArgs::UserPassword("letmein".into())]);
+    let printed = format!("{:?}", builder);
+    let tools = builder.build().unwrap();
+    for printed in [printed, format!("{:?}", tools), XpdfArgs::UserPassword("letmein".into()).to_string()] {
+        assert!(!printed.contains("hunter2") && !printed.contains("letmein"), "{}", printed);
+    }
+    assert_eq!(XpdfArgs::OwnerPassword("hunter2".into()).to_string(), "-opw ***");
+    assert_eq!(XpdfArgs::OwnerPassword("hunter2".into()).to_str(), "-opw hunter2");
+    assert_eq!(format!("{:?}", PdfPassword::user("letmein")), "PdfPassword { owner: None, user: Some(***) }");
+}
+
+#[test]
+fn test_written_files() {
+    //renders two pages next to the root like pdftoppm, lists and saves attachments like pdfdetach
+    struct FileRunner;
+    impl ToolRunner for FileRunner {
+        fn run(&self, program: &Path, args: &[String]) -> io::Result<ToolOutput> {
+            let tool = program.file_name().unwrap().to_string_lossy();
+            match (tool.as_ref(), args) {
+                ("pdftoppm", [.., _, root]) => {
+                    for page in 1..=2 {
+                        std::fs::write(format!("{}-{:06}.ppm", root, page), "P6")?;
+                    }
+                    Ok(ToolOutput::ok(""))
+                },
+                ("pdfdetach", [list, _]) if list == "-list" => Ok(ToolOutput::ok("2 embedded files\n1: ../../escape.txt\n2: /tmp/absolute.txt\n")),
+                ("pdfdetach", [save, _, o, file, _]) if save == "-save" && o == "-o" => std::fs::write(file, "data").map(|_| ToolOutput::ok("")),
+                _ => Ok(ToolOutput::ok("")),
+            }
+        }
+    }
+
+    let dir = std::env::temp_dir().join(format!("xpdf_written_{}", std::process::id()));
+    let out = dir.join("out");
+    std::fs::create_dir_all(&out).unwrap();
+    //a stale page of an earlier run and a page of "a-b.pdf"
+    std::fs::write(out.join("a-000003.ppm"), "P6").unwrap();
+    std::fs::write(out.join("a-b-000001.ppm"), "P6").unwrap();
+    let tools = XpdfTools::builder_unchecked(dir.clone(), &["pdftoppm", "pdfdetach"]).runner(FileRunner).build().unwrap();
+
+    let pages = tools.pdf_render(Path::new("a.pdf"), &out, 72).unwrap();
+    assert_eq!(pages, vec![out.join("a-000001.ppm"), out.join("a-000002.ppm")]);
+
+    let saved = tools.pdf_attachments_save(Path::new("a.pdf"), &out).unwrap();
+    let escaped = [dir.join("escape.txt").exists(), std::env::temp_dir().join("absolute.txt").exists() && !out.join("absolute.txt").exists()];
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(saved, vec![out.join("escape.txt"), out.join("absolute.txt")]);
+    assert_eq!(escaped, [false, false]);
+}
+
+#[test]
+fn test_text_to_file_and_writer() {
+    //writes the text to the output file argument or to stdout for "-" like pdftotext, fails for broken.pdf
+    struct WritingRunner;
+    impl ToolRunner for WritingRunner {
+        fn run(&self, _program: &Path, args: &[String]) -> io::Result<ToolOutput> {
+            match args {
+                [pdf, out] if pdf == "broken.pdf" && out == "-" => {
+                    Ok(ToolOutput { stdout: b"partial".to_vec(), ..ToolOutput::failed(1, "Syntax Error: Couldn't find trailer dictionary\n") })
+                },
+                [_, out] if out == "-" => Ok(ToolOutput::ok("page one\x0c")),
+                [pdf, out] if pdf == "broken.pdf" => {
+                    std::fs::write(out, "partial")?;
+                    Ok(ToolOutput::failed(1, "Syntax Error: Couldn't find trailer dictionary\n"))
+                },
+                [_, out] => std::fs::write(out, "page one\x0c").map(|_| ToolOutput::ok("")),
+                _ => Ok(ToolOutput::ok("")),
+            }
+        }
+    }
+
+    let dir = std::env::temp_dir().join(format!("xpdf_text_out_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let tools = XpdfTools::builder_unchecked(dir.clone(), &["pdftotext"]).runner(WritingRunner).build().unwrap();
+
+    let out = dir.join("a.txt");
+    tools.pdf_text_to_file(Path::new("a.pdf"), &out).unwrap();
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "page one\x0c");
+
+    let error = tools.pdf_text_to_file(Path::new("broken.pdf"), &out).unwrap_err();
+    assert_eq!(error.kind(), PdfErrorKind::PdfToTextError);
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "page one\x0c");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    let mut sink = vec![];
+    assert_eq!(tools.pdf_text_to_writer(Path::new("a.pdf"), &mut sink).unwrap(), 9);
+    assert_eq!(sink, b"page one\x0c");
+    let mut sink = vec![];
+    let error = tools.pdf_text_to_writer(Path::new("broken.pdf"), &mut sink).unwrap_err();
+    assert_eq!(error.kind(), PdfErrorKind::PdfToTextError);
+    //the text is streamed, what the tool wrote before failing stays in the writer
+    assert_eq!(sink, b"partial");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_builder_tool_files() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("xpdf_builder_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    //stray files with a tool stem next to the usable tool are ignored, even executable ones
+    let tool = dir.join("pdfinfo");
+    for path in [&tool, &dir.join("pdfinfo.bak"), &dir.join("pdftops.sh")] {
+        fs::write(path, "#!/bin/sh\necho pdfinfo version 4.04\n").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    fs::write(dir.join("pdftops.txt"), "notes").unwrap();
+
+    let builder = XpdfTools::builder(dir.clone()).unwrap();
+    let checks: Vec<_> = builder.tool_report().checks.iter().map(|c| (c.tool.as_str(), c.path.clone(), c.status)).collect();
+    assert_eq!(checks, vec![("pdfinfo", tool.clone(), ToolStatus::Accepted)]);
+    let tools = builder.build().unwrap();
+    assert_eq!(tools.tool_path("pdfinfo"), tool);
+
+    //a broken tool fails the build
+    fs::write(dir.join("pdftotext"), "not a binary").unwrap();
+    let error = XpdfTools::builder(dir.clone()).unwrap().build().unwrap_err();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(error.kind(), PdfErrorKind::ToolNotUsable);
+    assert!(error.process_message.contains("pdftotext"), "{}", error.process_message);
+}
@@ -0,0 +1,174 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::runner::ToolOutput;
+
+//messages of the tools when an allocation or open failed, xpdf uses gmem, poppler C++ new
+const OUT_OF_MEMORY: &[&str] = &["Out of memory", "bad_alloc", "Cannot allocate memory"];
+const TOO_MANY_FILES: &str = "Too many open files";
+
+/// Limits set with setrlimit in each tool process before it starts, unix only
+/// Unset limits are inherited from the calling process.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResourceLimits {
+    address_space: Option<u64>,
+    cpu_time: Option<Duration>,
+    file_size: Option<u64>,
+    open_files: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum virtual memory in bytes (RLIMIT_AS)
+    pub fn address_space(mut self, bytes: u64) -> Self {
+        self.address_space = Some(bytes);
+        self
+    }
+
+    /// CPU time, rounded up to whole seconds (RLIMIT_CPU)
+    /// The tool gets SIGXCPU at the limit and SIGKILL a second later
+    /// Only SIGXCPU is reported as a breach, a SIGKILL may as well come from the OOM killer or another process
+    pub fn cpu_time(mut self, time: Duration) -> Self {
+        self.cpu_time = Some(time);
+        self
+    }
+
+    /// Maximum size in bytes of a file written by the tool (RLIMIT_FSIZE)
+    pub fn file_size(mut self, bytes: u64) -> Self {
+        self.file_size = Some(bytes);
+        self
+    }
+
+    /// Maximum number of open file descriptors (RLIMIT_NOFILE)
+    pub fn open_files(mut self, count: u64) -> Self {
+        self.open_files = Some(count);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn cpu_seconds(&self) -> Option<u64> {
+        self.cpu_time.map(|time| time.as_secs() + u64::from(time.subsec_nanos() > 0)).map(|secs| secs.max(1))
+    }
+
+    /// Sets the limits on the current process, called in the child between fork and exec
+    /// Only async-signal-safe calls are allowed here: no allocation, no locks
+    #[cfg(unix)]
+    pub(crate) fn apply(&self) -> io::Result<()> {
+        let limits = [
+            (libc::RLIMIT_AS, self.address_space, self.address_space),
+            (libc::RLIMIT_CPU, self.cpu_seconds(), self.cpu_seconds().map(|secs| secs + 1)),
+            (libc::RLIMIT_FSIZE, self.file_size, self.file_size),
+            (libc::RLIMIT_NOFILE, self.open_files, self.open_files),
+        ];
+        for (resource, soft, hard) in limits {
+            if let (Some(soft), Some(hard)) = (soft, hard) {
+                let limit = libc::rlimit { rlim_cur: soft as libc::rlim_t, rlim_max: hard as libc::rlim_t };
+                //SAFETY: setrlimit only reads the struct
+                if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Which limit the tool ran into, judged by the terminating signal or the error message on stderr
+    pub fn exceeded(&self, output: &ToolOutput) -> Option<String> {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        #[cfg(unix)]
+        if let (Some(secs), Some(libc::SIGXCPU)) = (self.cpu_seconds(), output.signal) {
+            return Some(format!("CPU time limit of {}s exceeded", secs));
+        }
+        #[cfg(unix)]
+        if let (Some(bytes), Some(libc::SIGXFSZ)) = (self.file_size, output.signal) {
+            return Some(format!("file size limit of {} bytes exceeded", bytes));
+        }
+        if let Some(bytes) = self.address_space.filter(|_| OUT_OF_MEMORY.iter().any(|m| stderr.contains(m))) {
+            return Some(format!("address space limit of {} bytes exceeded", bytes));
+        }
+        if let Some(count) = self.open_files.filter(|_| stderr.contains(TOO_MANY_FILES)) {
+            return Some(format!("open files limit of {} exceeded", count));
+        }
+        None
+    }
+}
+
+/// What a call returns when a tool writes more than the output limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputOverflow {
+    /// fail with PdfErrorKind::OutputLimitExceeded
+    Fail,
+    /// return the output up to the limit, the files written so far for tools writing files
+    Truncate,
+}
+
+/// The files a tool writes: those in the folder with names starting with the prefix
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputFiles {
+    pub dir: PathBuf,
+    pub prefix: String,
+}
+
+impl OutputFiles {
+    /// The files named like `<root>-000001.png`, as written by pdfimages and the renderers, or the root itself
+    pub fn root(root: &Path) -> Self {
+        OutputFiles {
+            dir: root.parent().map(Path::to_path_buf).unwrap_or_default(),
+            prefix: root.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        }
+    }
+
+    /// Every file in the folder
+    pub fn dir(dir: &Path) -> Self {
+        OutputFiles { dir: dir.into(), prefix: String::new() }
+    }
+
+    /// Total size of the files
+    pub fn size(&self) -> u64 {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return 0;
+        };
+        entries.filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(&self.prefix))
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+}
+
+/// The most a single tool invocation may write to stdout and, if given, into its output files
+/// Files present before the tool started are not counted
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputLimit {
+    pub max_bytes: u64,
+    pub files: Option<OutputFiles>,
+}
+
+#[test]
+fn test_limit_exceeded() {
+    let limits = ResourceLimits::new().address_space(1 << 30).cpu_time(Duration::from_millis(1500));
+    assert_eq!(limits.cpu_seconds(), Some(2));
+    assert!(ResourceLimits::new().is_empty() && !limits.is_empty());
+
+    let oom = ToolOutput::failed(1, "Syntax Error: ...\nOut of memory\n");
+    assert_eq!(limits.exceeded(&oom).unwrap(), "address space limit of 1073741824 bytes exceeded");
+    assert_eq!(ResourceLimits::new().exceeded(&oom), None);
+    assert_eq!(limits.exceeded(&ToolOutput::failed(1, "Syntax Error: Couldn't find trailer dictionary\n")), None);
+
+    #[cfg(unix)]
+    {
+        let killed = ToolOutput { status: None, signal: Some(libc::SIGXCPU), ..Default::default() };
+        assert_eq!(limits.exceeded(&killed).unwrap(), "CPU time limit of 2s exceeded");
+        let oom_killed = ToolOutput { status: None, signal: Some(libc::SIGKILL), ..Default::default() };
+        assert_eq!(limits.exceeded(&oom_killed), None);
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use crate::secret::Secret;
+use crate::types::XpdfArgs;
+
+/// A candidate owner/user password pair for an encrypted pdf file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfPassword {
+    pub owner: Option<Secret>,
+    pub user: Option<Secret>,
+}
+
+impl PdfPassword {
+    pub fn owner(password: &str) -> Self {
+        PdfPassword { owner: Some(password.into()), user: None }
+    }
+    pub fn user(password: &str) -> Self {
+        PdfPassword { owner: None, user: Some(password.into()) }
+    }
+
+    pub fn to_args(&self) -> Vec<XpdfArgs> {
+        let mut args = vec![];
+        if let Some(owner) = &self.owner {
+            args.push(XpdfArgs::OwnerPassword(owner.clone()));
+        }
+        if let Some(user) = &self.user {
+            args.push(XpdfArgs::UserPassword(user.clone()));
+        }
+        args
+    }
+}
+
+/// Supplies candidate passwords for a pdf file
+/// XpdfTools tries them in order when a tool reports an incorrect password
+pub trait PasswordProvider {
+    fn passwords(&self, pdf_file: &Path) -> Vec<PdfPassword>;
+}
+
+impl<F> PasswordProvider for F
+where
+    F: Fn(&Path) -> Vec<PdfPassword>,
+{
+    fn passwords(&self, pdf_file: &Path) -> Vec<PdfPassword> {
+        self(pdf_file)
+    }
+}
+
+/// xpdf and poppler both report a missing or wrong password on stderr with this message
+pub fn is_password_error(stderr: &[u8]) -> bool {
+    String::from_utf8_lossy(stderr).contains("Incorrect password")
+}
+
+#[test]
+fn test_password_args() {
+    let pwd = PdfPassword { owner: Some("o".into()), user: Some("u".into()) };
+    assert_eq!(pwd.to_args(), vec![XpdfArgs::OwnerPassword("o".into()), XpdfArgs::UserPassword("u".into())]);
+    assert!(PdfPassword::default().to_args().is_empty());
+}
+
+#[test]
+fn test_password_error() {
+    assert!(is_password_error(b"Command Line Error: Incorrect password\n"));
+    assert!(!is_password_error(b"Syntax Error: Couldn't find trailer dictionary\n"));
+
+    let provider = |_: &Path| vec![PdfPassword::user("secret")];
+    assert_eq!(provider.passwords(Path::new("a.pdf")), vec![PdfPassword::user("secret")]);
+}
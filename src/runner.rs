@@ -0,0 +1,429 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::limits::{OutputLimit, ResourceLimits};
+use crate::sandbox::{self, SandboxPolicy, WorkDir};
+
+/// Captured result of a tool process
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolOutput {
+    /// exit code, None if the process was terminated by a signal
+    pub status: Option<i32>,
+    /// the signal which terminated the process, unix only
+    pub signal: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// the tool was stopped at the output limit, stdout holds the output up to the limit
+    pub truncated: bool,
+}
+
+impl ToolOutput {
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+
+    /// Exit code 0 with the given stdout
+    pub fn ok(stdout: impl Into<Vec<u8>>) -> Self {
+        ToolOutput { status: Some(0), stdout: stdout.into(), ..Default::default() }
+    }
+
+    /// The given exit code and stderr
+    pub fn failed(status: i32, stderr: impl Into<Vec<u8>>) -> Self {
+        ToolOutput { status: Some(status), stderr: stderr.into(), ..Default::default() }
+    }
+}
+
+/// Runs a tool program with its arguments, XpdfTools spawns every process through it
+pub trait ToolRunner {
+    fn run(&self, program: &Path, args: &[String]) -> io::Result<ToolOutput>;
+
+    /// Like run, but stops the tool once its stdout or its output files exceed the limit
+    /// The default runs the tool unlimited and cuts stdout afterwards, output files are not checked
+    fn run_limited(&self, program: &Path, args: &[String], limit: &OutputLimit) -> io::Result<ToolOutput> {
+        let mut output = self.run(program, args)?;
+        if output.stdout.len() as u64 > limit.max_bytes {
+            output.stdout.truncate(limit.max_bytes as usize);
+            output.truncated = true;
+        }
+        Ok(output)
+    }
+
+    /// Like run_limited without counting output files, stdout goes into the writer instead of the result
+    /// The default runs the tool and writes its stdout afterwards, ProcessRunner copies it as it arrives
+    fn run_streamed(&self, program: &Path, args: &[String], limit: Option<&OutputLimit>, out: &mut dyn Write) -> io::Result<ToolOutput> {
+        let mut output = match limit {
+            Some(limit) => self.run_limited(program, args, limit)?,
+            None => self.run(program, args)?,
+        };
+        out.write_all(&output.stdout)?;
+        output.stdout.clear();
+        Ok(output)
+    }
+}
+
+/// The default runner spawning a child process
+#[derive(Debug, Clone, Default)]
+pub struct ProcessRunner {
+    limits: ResourceLimits,
+    sandbox: Option<SandboxPolicy>,
+}
+
+impl ProcessRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits applied to every child process
+    pub fn limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Isolates every child process, see SandboxPolicy
+    pub fn sandbox(mut self, policy: SandboxPolicy) -> Self {
+        self.sandbox = Some(policy);
+        self
+    }
+}
+
+impl ProcessRunner {
+    //The command with the sandbox and the limits set up, the work dir has to live until the tool exited
+    fn command(&self, program: &Path, args: &[String]) -> io::Result<(Command, Option<WorkDir>)> {
+        //a relative program path would be resolved in the sandbox working directory
+        let program = match (&self.sandbox, program.components().count()) {
+            (Some(_), 2..) => std::path::absolute(program)?,
+            _ => program.to_path_buf(),
+        };
+        let mut command = Command::new(program);
+        command.args(args);
+        let work_dir = self.sandbox.as_ref().map(|policy| policy.prepare(&mut command)).transpose()?;
+        #[cfg(unix)]
+        if !self.limits.is_empty() || self.sandbox.is_some() {
+            use std::os::unix::process::CommandExt;
+            let limits = self.limits;
+            let sandboxed = self.sandbox.is_some();
+            //SAFETY: the hook only makes async-signal-safe calls (prctl, fcntl, setrlimit)
+            unsafe {
+                command.pre_exec(move || {
+                    if sandboxed {
+                        sandbox::harden()?;
+                    }
+                    limits.apply()
+                });
+            }
+        }
+        Ok((command, work_dir))
+    }
+
+    //Kills the tool, in a sandbox with the processes it started
+    fn kill(&self, child: &mut Child) {
+        #[cfg(unix)]
+        if self.sandbox.is_some() {
+            //SAFETY: kill only takes integers, the sandboxed child leads its own process group
+            unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) };
+        }
+        let _ = child.kill();
+    }
+}
+
+impl ToolRunner for ProcessRunner {
+    fn run(&self, program: &Path, args: &[String]) -> io::Result<ToolOutput> {
+        let (mut command, _work_dir) = self.command(program, args)?;
+        let output = command.output()?;
+        Ok(ToolOutput {
+            status: output.status.code(),
+            signal: signal(&output.status),
+            stdout: output.stdout,
+            stderr: output.stderr,
+            truncated: false,
+        })
+    }
+
+    //Reads stdout and stderr in threads while watching the output size, the tool is killed at the limit
+    fn run_limited(&self, program: &Path, args: &[String], limit: &OutputLimit) -> io::Result<ToolOutput> {
+        let (mut command, _work_dir) = self.command(program, args)?;
+        let files_before = limit.files.as_ref().map(|files| files.size()).unwrap_or(0);
+        let mut child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+        let max = limit.max_bytes;
+        let stdout_bytes = Arc::new(AtomicU64::new(0));
+        let stdout = child.stdout.take().map(|pipe| {
+            let seen = stdout_bytes.clone();
+            thread::spawn(move || read_capped(pipe, max, &seen))
+        });
+        let stderr = child.stderr.take().map(|pipe| thread::spawn(move || read_capped(pipe, max, &AtomicU64::new(0))));
+
+        //polled with a growing interval, most tools are done within the first milliseconds
+        let mut interval = Duration::from_millis(1);
+        let mut truncated = false;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            let written = limit.files.as_ref().map(|files| files.size().saturating_sub(files_before)).unwrap_or(0);
+            if stdout_bytes.load(Ordering::Relaxed) > max || written > max {
+                truncated = true;
+                self.kill(&mut child);
+                break child.wait()?;
+            }
+            thread::sleep(interval);
+            interval = (interval * 2).min(Duration::from_millis(25));
+        };
+
+        let stdout = join(stdout)?;
+        let stderr = join(stderr)?;
+        Ok(ToolOutput {
+            status: status.code(),
+            signal: signal(&status),
+            truncated: truncated || stdout_bytes.load(Ordering::Relaxed) > max,
+            stdout,
+            stderr,
+        })
+    }
+
+    //Copies stdout into the writer on this thread while a thread reads stderr
+    //The tool is killed at the limit or when the writer fails
+    fn run_streamed(&self, program: &Path, args: &[String], limit: Option<&OutputLimit>, out: &mut dyn Write) -> io::Result<ToolOutput> {
+        let (mut command, _work_dir) = self.command(program, args)?;
+        let mut child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+        let max = limit.map(|limit| limit.max_bytes).unwrap_or(u64::MAX);
+        let stderr = child.stderr.take().map(|pipe| thread::spawn(move || read_capped(pipe, max, &AtomicU64::new(0))));
+        let copied = match child.stdout.take() {
+            Some(pipe) => copy_capped(pipe, out, max),
+            None => Ok(false),
+        };
+        let truncated = match copied {
+            Ok(truncated) => truncated,
+            Err(e) => {
+                self.kill(&mut child);
+                let _ = child.wait();
+                return Err(e);
+            },
+        };
+        if truncated {
+            self.kill(&mut child);
+        }
+        let status = child.wait()?;
+        Ok(ToolOutput {
+            status: status.code(),
+            signal: signal(&status),
+            stdout: vec![],
+            stderr: join(stderr)?,
+            truncated,
+        })
+    }
+}
+
+fn join(reader: Option<thread::JoinHandle<io::Result<Vec<u8>>>>) -> io::Result<Vec<u8>> {
+    match reader {
+        Some(reader) => reader.join().unwrap_or_else(|_| Err(io::Error::other("output reader panicked"))),
+        None => Ok(vec![]),
+    }
+}
+
+//Copies the first max bytes into the writer, returns whether the tool wrote more
+fn copy_capped(mut pipe: impl Read, out: &mut dyn Write, max: u64) -> io::Result<bool> {
+    let mut copied = 0u64;
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = match pipe.read(&mut buffer) {
+            Ok(0) => return Ok(false),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let keep = (max - copied).min(n as u64) as usize;
+        out.write_all(&buffer[..keep])?;
+        copied += keep as u64;
+        if keep < n {
+            return Ok(true);
+        }
+    }
+}
+
+//Counts the bytes passed to the inner writer
+pub(crate) struct CountingWriter<'a> {
+    pub(crate) inner: &'a mut dyn Write,
+    pub(crate) count: u64,
+}
+
+impl Write for CountingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+//Keeps the first max bytes, the rest is read and dropped so the tool never blocks on a full pipe
+fn read_capped(mut pipe: impl Read, max: u64, seen: &AtomicU64) -> io::Result<Vec<u8>> {
+    let mut kept = vec![];
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = match pipe.read(&mut buffer) {
+            Ok(0) => return Ok(kept),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let keep = (max.saturating_sub(kept.len() as u64) as usize).min(n);
+        kept.extend_from_slice(&buffer[..keep]);
+        seen.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(unix)]
+fn signal(status: &std::process::ExitStatus) -> Option<i32> {
+    std::os::unix::process::ExitStatusExt::signal(status)
+}
+
+#[cfg(not(unix))]
+fn signal(_status: &std::process::ExitStatus) -> Option<i32> {
+    None
+}
+
+/// A recorded call of a FakeRunner
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl Invocation {
+    /// The tool name, the file stem of the program
+    pub fn tool(&self) -> String {
+        self.program.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    outputs: BTreeMap<String, VecDeque<ToolOutput>>,
+    invocations: Vec<Invocation>,
+}
+
+/// Replays canned outputs per tool and records every invocation
+/// Outputs queued for a tool are returned in order, the last one is repeated
+/// Clones share their state, so a clone given to the builder can be inspected afterwards
+#[derive(Debug, Clone, Default)]
+pub struct FakeRunner {
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl FakeRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_output(self, tool: &str, output: ToolOutput) -> Self {
+        self.push_output(tool, output);
+        self
+    }
+
+    pub fn push_output(&self, tool: &str, output: ToolOutput) {
+        self.state.lock().unwrap().outputs.entry(tool.into()).or_default().push_back(output);
+    }
+
+    pub fn invocations(&self) -> Vec<Invocation> {
+        self.state.lock().unwrap().invocations.clone()
+    }
+}
+
+impl ToolRunner for FakeRunner {
+    fn run(&self, program: &Path, args: &[String]) -> io::Result<ToolOutput> {
+        let invocation = Invocation { program: program.into(), args: args.to_vec() };
+        let tool = invocation.tool();
+
+        let mut state = self.state.lock().unwrap();
+        state.invocations.push(invocation);
+
+        let queue = state.outputs.get_mut(&tool)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no canned output for {}", tool)))?;
+        match queue.len() {
+            0 => Err(io::Error::new(io::ErrorKind::NotFound, format!("no canned output for {}", tool))),
+            1 => Ok(queue[0].clone()),
+            _ => Ok(queue.pop_front().unwrap()),
+        }
+    }
+}
+
+#[test]
+fn test_fake_runner() {
+    let runner = FakeRunner::new()
+        .with_output("pdfinfo", ToolOutput::failed(1, "Command Line Error: Incorrect password\n"))
+        .with_output("pdfinfo", ToolOutput::ok("Pages: 1\n"));
+    let shared = runner.clone();
+
+    let program = Path::new("/opt/xpdf/pdfinfo");
+    assert_eq!(runner.run(program, &["a.pdf".into()]).unwrap().status, Some(1));
+    assert!(runner.run(program, &["b.pdf".into()]).unwrap().success());
+    assert!(runner.run(program, &["c.pdf".into()]).unwrap().success());
+    assert!(runner.run(Path::new("pdftotext"), &[]).is_err());
+
+    let invocations = shared.invocations();
+    assert_eq!(invocations.len(), 4);
+    assert_eq!(invocations[1], Invocation { program: program.into(), args: vec!["b.pdf".into()] });
+    assert_eq!(invocations[3].tool(), "pdftotext");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_process_runner_limits() {
+    let runner = ProcessRunner::new().limits(ResourceLimits::new().open_files(64).cpu_time(std::time::Duration::from_secs(30)));
+    let output = runner.run(Path::new("/bin/sh"), &["-c".into(), "ulimit -n; ulimit -t".into()]).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "64\n30\n");
+
+    let killed = ProcessRunner::new().run(Path::new("/bin/sh"), &["-c".into(), "kill -XFSZ $$".into()]).unwrap();
+    assert_eq!((killed.status, killed.signal), (None, Some(libc::SIGXFSZ)));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_process_runner_output_limit() {
+    use crate::limits::OutputFiles;
+
+    let runner = ProcessRunner::new();
+    let sh = Path::new("/bin/sh");
+    let limit = OutputLimit { max_bytes: 1000, files: None };
+
+    let small = runner.run_limited(sh, &["-c".into(), "echo hi; echo oops >&2".into()], &limit).unwrap();
+    assert_eq!((small.stdout.as_slice(), small.stderr.as_slice(), small.truncated, small.status), (&b"hi\n"[..], &b"oops\n"[..], false, Some(0)));
+
+    let endless = runner.run_limited(sh, &["-c".into(), "exec yes".into()], &limit).unwrap();
+    assert!(endless.truncated);
+
+    assert_eq!(endless.stdout, b"y\n".repeat(500));
+
+    let mut streamed = vec![];
+    let small_streamed = runner.run_streamed(sh, &["-c".into(), "echo hi; echo oops >&2".into()], None, &mut streamed).unwrap();
+    assert_eq!((streamed.as_slice(), small_streamed.stdout.len(), small_streamed.stderr.as_slice(), small_streamed.truncated), (&b"hi\n"[..], 0, &b"oops\n"[..], false));
+    let mut streamed = vec![];
+    let endless_streamed = runner.run_streamed(sh, &["-c".into(), "exec yes".into()], Some(&limit), &mut streamed).unwrap();
+    assert_eq!((streamed, endless_streamed.truncated), (b"y\n".repeat(500), true));
+
+    //in a sandbox the processes started by the tool are killed with it
+    let sandboxed = ProcessRunner::new().sandbox(SandboxPolicy::new().allow_env("PATH"));
+    assert!(sandboxed.run_limited(sh, &["-c".into(), "yes | cat".into()], &limit).unwrap().truncated);
+
+    let dir = std::env::temp_dir().join(format!("xpdf_output_limit_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("page-000.txt"), vec![b'x'; 5000]).unwrap();
+    let limit = OutputLimit { max_bytes: 10_000, files: Some(OutputFiles::root(&dir.join("page"))) };
+    let script = format!("while true; do echo 0123456789 >> {}/page-001.txt; done", dir.display());
+    let writing = runner.run_limited(sh, &["-c".into(), script], &limit).unwrap();
+    assert!(writing.truncated && writing.stdout.is_empty());
+    //the file present before is not counted
+    assert!(std::fs::metadata(dir.join("page-001.txt")).unwrap().len() > 5000);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,143 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static WORK_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// How the tool processes are isolated when working on untrusted pdf files
+/// Every process runs with a cleared environment except the allowed variables,
+/// in a new empty working directory removed afterwards, without stdin and in its own process group.
+/// On unix file descriptors besides stdout and stderr are not inherited,
+/// on Linux the process also gets PR_SET_NO_NEW_PRIVS, so setuid binaries can not raise its privileges.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SandboxPolicy {
+    allowed_env: Vec<String>,
+    temp_dir: Option<PathBuf>,
+}
+
+impl SandboxPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Passes the variable on with its value in the calling process, e.g. LANG or TZ
+    pub fn allow_env(mut self, name: &str) -> Self {
+        self.allowed_env.push(name.into());
+        self
+    }
+
+    /// The folder in which the working directories are created, default the temp folder
+    pub fn temp_dir(mut self, dir: &Path) -> Self {
+        self.temp_dir = Some(dir.into());
+        self
+    }
+
+    pub fn allowed_env(&self) -> &[String] {
+        &self.allowed_env
+    }
+
+    /// Sets up the command, the returned directory is its working directory until dropped
+    pub(crate) fn prepare(&self, command: &mut Command) -> io::Result<WorkDir> {
+        let root = self.temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+        let dir = WorkDir::create(&root)?;
+
+        command.env_clear();
+        for name in &self.allowed_env {
+            if let Some(value) = std::env::var_os(name) {
+                command.env(name, value);
+            }
+        }
+        command.current_dir(&dir.0).stdin(Stdio::null());
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(command, 0);
+        Ok(dir)
+    }
+}
+
+/// Called in the child between fork and exec, only async-signal-safe calls are allowed
+#[cfg(unix)]
+pub(crate) fn harden() -> io::Result<()> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    //SAFETY: prctl with PR_SET_NO_NEW_PRIVS takes no pointers
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    close_inherited_fds();
+    Ok(())
+}
+
+//Marks every descriptor above stderr close-on-exec. Closing them here would also close
+//the pipe std uses to report a failed exec, which is close-on-exec already.
+#[cfg(unix)]
+fn close_inherited_fds() {
+    #[cfg(target_os = "linux")]
+    //SAFETY: close_range only takes integers, it fails on kernels older than 5.11
+    if unsafe { libc::syscall(libc::SYS_close_range, 3u32, u32::MAX, libc::CLOSE_RANGE_CLOEXEC) } == 0 {
+        return;
+    }
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    //SAFETY: getrlimit writes into the struct, fcntl on a closed descriptor fails with EBADF
+    let max_fd = match unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } {
+        0 if limit.rlim_cur != libc::RLIM_INFINITY => limit.rlim_cur as libc::c_int,
+        _ => 65536,
+    };
+    for fd in 3..max_fd {
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+}
+
+/// An empty working directory, removed with its contents on drop
+pub(crate) struct WorkDir(PathBuf);
+
+impl WorkDir {
+    fn create(root: &Path) -> io::Result<Self> {
+        let path = root.join(format!("xpdf-sandbox-{}-{}", std::process::id(), WORK_DIRS.fetch_add(1, Ordering::Relaxed)));
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&path)?;
+        Ok(WorkDir(path))
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_sandboxed_process() {
+    use crate::runner::{ProcessRunner, ToolRunner};
+
+    //a descriptor without close-on-exec, as opened by a library not using std
+    //numbered high to not be taken for a descriptor of the shell
+    let leaked = unsafe {
+        let fd = libc::open(c"/dev/null".as_ptr(), libc::O_RDONLY);
+        let high = libc::fcntl(fd, libc::F_DUPFD, 200);
+        libc::close(fd);
+        high
+    };
+    assert!(leaked >= 200);
+
+    let runner = ProcessRunner::new().sandbox(SandboxPolicy::new().allow_env("PATH"));
+    let run = |script: &str| {
+        let output = runner.run(Path::new("/bin/sh"), &["-c".into(), script.into()]).unwrap();
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+
+    let env = runner.run(Path::new("/usr/bin/env"), &[]).unwrap();
+    assert!(String::from_utf8_lossy(&env.stdout).lines().all(|line| line.starts_with("PATH=")));
+    let work_dir = PathBuf::from(run("pwd"));
+    assert!(work_dir.starts_with(std::env::temp_dir()) && !work_dir.exists());
+    assert_eq!(run("ls -A | wc -l"), "0");
+    assert_eq!(run("grep NoNewPrivs /proc/self/status | cut -f2"), "1");
+    assert!(!run("ls /proc/self/fd").split_whitespace().any(|fd| fd == leaked.to_string()));
+    //the shell leads its own process group
+    assert_eq!(run("read -r pid comm state ppid pgrp rest < /proc/self/stat; [ $pid = $pgrp ] && echo leader"), "leader");
+
+    unsafe { libc::close(leaked) };
+}
@@ -0,0 +1,542 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::xpdf_text::PdfText;
+use crate::{PdfError, PdfErrorKind, XpdfTools};
+
+const SEGMENT_HEADER: &str = "xpdf-search-segment 1";
+const SEGMENT_EXTENSION: &str = "seg";
+const SNIPPET_CHARS: usize = 40;
+
+/// A page of an indexed document matching a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub path: PathBuf,
+    pub page: usize,
+    /// text around the first matching term, on one line
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct IndexedDoc {
+    path: PathBuf,
+    text: PdfText,
+}
+
+//(document id, page index)
+type PageRef = (u32, usize);
+
+/// Inverted index over the pages of pdf documents
+/// Terms are lower cased alphanumeric words, their positions are kept for phrase queries.
+/// The page texts are stored with the index for snippets.
+/// `save` writes one segment file per document into a folder, with its page texts and postings,
+/// only documents added or removed since the last save or load are written or deleted.
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    docs: BTreeMap<u32, IndexedDoc>,
+    postings: BTreeMap<String, BTreeMap<PageRef, Vec<usize>>>,
+    next_id: u32,
+    //the folder the segments were last saved to or loaded from, and the changes since
+    folder: Option<PathBuf>,
+    added: BTreeSet<u32>,
+    removed: BTreeSet<u32>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Extracts the text with pdftotext and indexes it, an already indexed path is replaced
+    pub fn add_document(&mut self, tools: &XpdfTools, pdf_file: &Path) -> Result<(), PdfError> {
+        let text = tools.pdf_text_pages(pdf_file)?;
+        self.add_text(pdf_file, text);
+        Ok(())
+    }
+
+    /// Indexes already extracted text, an already indexed path is replaced
+    pub fn add_text(&mut self, path: &Path, text: PdfText) {
+        self.remove(path);
+        let id = self.next_id;
+        self.next_id += 1;
+
+        for (page, page_text) in text.pages.iter().enumerate() {
+            for (position, (term, _)) in tokenize(page_text).into_iter().enumerate() {
+                self.postings.entry(term).or_default().entry((id, page)).or_default().push(position);
+            }
+        }
+        self.docs.insert(id, IndexedDoc { path: path.into(), text });
+        self.added.insert(id);
+    }
+
+    /// Removes the document, returns false if it was not indexed
+    pub fn remove(&mut self, path: &Path) -> bool {
+        let Some(id) = self.docs.iter().find(|(_, doc)| doc.path == path).map(|(id, _)| *id) else {
+            return false;
+        };
+        self.docs.remove(&id);
+        if !self.added.remove(&id) {
+            self.removed.insert(id);
+        }
+        self.postings.retain(|_, pages| {
+            pages.retain(|(doc, _), _| *doc != id);
+            !pages.is_empty()
+        });
+        true
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.docs.values().any(|doc| doc.path == path)
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = &Path> {
+        self.docs.values().map(|doc| doc.path.as_path())
+    }
+
+    /// Pages matching the query, ordered by document and page
+    /// Syntax: words, "quoted phrases", AND (also implicit between terms), OR, NOT or a leading -,
+    /// and parentheses. Matching ignores case.
+    pub fn search(&self, query: &str) -> Result<Vec<SearchHit>, PdfError> {
+        let query = Query::parse(query)?;
+        let terms = query.positive_terms();
+
+        let hits = self.evaluate(&query).into_iter().map(|(id, page)| {
+            let doc = &self.docs[&id];
+            SearchHit {
+                path: doc.path.clone(),
+                page: doc.text.first_page + page,
+                snippet: snippet(&doc.text.pages[page], &terms),
+            }
+        }).collect();
+        Ok(hits)
+    }
+
+    fn evaluate(&self, query: &Query) -> BTreeSet<PageRef> {
+        match query {
+            Query::Term(term) => self.postings.get(term).map(|pages| pages.keys().copied().collect()).unwrap_or_default(),
+            Query::Phrase(terms) => self.phrase(terms),
+            Query::And(left, right) => self.evaluate(left).intersection(&self.evaluate(right)).copied().collect(),
+            Query::Or(left, right) => self.evaluate(left).union(&self.evaluate(right)).copied().collect(),
+            Query::Not(inner) => {
+                let excluded = self.evaluate(inner);
+                self.all_pages().filter(|page| !excluded.contains(page)).collect()
+            },
+        }
+    }
+
+    //Pages where the terms follow each other
+    fn phrase(&self, terms: &[String]) -> BTreeSet<PageRef> {
+        let Some(first) = terms.first().and_then(|t| self.postings.get(t)) else {
+            return BTreeSet::new();
+        };
+        first.iter()
+            .filter(|(page, starts)| starts.iter().any(|start| {
+                terms.iter().enumerate().skip(1).all(|(offset, term)| {
+                    self.postings.get(term)
+                        .and_then(|pages| pages.get(page))
+                        .is_some_and(|positions| positions.contains(&(start + offset)))
+                })
+            }))
+            .map(|(page, _)| *page)
+            .collect()
+    }
+
+    fn all_pages(&self) -> impl Iterator<Item = PageRef> + '_ {
+        self.docs.iter().flat_map(|(id, doc)| (0..doc.text.pages.len()).map(move |page| (*id, page)))
+    }
+
+    /// Writes the index into the folder, one `<id>.seg` file per document
+    /// Saving again into the same folder only writes the added documents and deletes the removed ones.
+    pub fn save(&mut self, folder: &Path) -> io::Result<()> {
+        fs::create_dir_all(folder)?;
+        let changed_only = self.folder.as_deref() == Some(folder);
+        if !changed_only {
+            //a different folder gets every document, segments of another index are removed
+            for (path, id) in segment_files(folder)? {
+                if !self.docs.contains_key(&id) {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+
+        for (id, doc) in &self.docs {
+            if !changed_only || self.added.contains(id) {
+                let file = segment_path(folder, *id);
+                let tmp = file.with_extension("tmp");
+                fs::write(&tmp, self.segment(*id, doc))?;
+                fs::rename(tmp, file)?;
+            }
+        }
+        if changed_only {
+            for id in &self.removed {
+                match fs::remove_file(segment_path(folder, *id)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => (),
+                }
+            }
+        }
+
+        self.folder = Some(folder.into());
+        self.added.clear();
+        self.removed.clear();
+        Ok(())
+    }
+
+    //The document, its page texts and its postings as `term<TAB>page:position,position;page:position`
+    fn segment(&self, id: u32, doc: &IndexedDoc) -> String {
+        let mut out = format!("{}\ndoc\t{}\t{}\n", SEGMENT_HEADER, escape(&doc.path.to_string_lossy()), doc.text.first_page);
+        for page in &doc.text.pages {
+            out.push_str(&format!("page\t{}\n", escape(page)));
+        }
+        for (term, pages) in &self.postings {
+            let postings: Vec<String> = pages.range((id, 0)..=(id, usize::MAX))
+                .map(|((_, page), positions)| format!("{}:{}", page, positions.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(",")))
+                .collect();
+            if !postings.is_empty() {
+                out.push_str(&format!("term\t{}\t{}\n", escape(term), postings.join(";")));
+            }
+        }
+        out
+    }
+
+    /// Reads the segments saved into the folder, the postings are taken as stored
+    pub fn load(folder: &Path) -> io::Result<Self> {
+        let mut index = SearchIndex::new();
+        for (path, id) in segment_files(folder)? {
+            let content = fs::read_to_string(&path)?;
+            index.load_segment(id, &content)
+                .map_err(|line| io::Error::new(io::ErrorKind::InvalidData, format!("invalid search index line in {}: {}", path.display(), line)))?;
+            index.next_id = index.next_id.max(id + 1);
+        }
+        index.folder = Some(folder.into());
+        Ok(index)
+    }
+
+    //Err is the invalid line
+    fn load_segment(&mut self, id: u32, content: &str) -> Result<(), String> {
+        let mut lines = content.lines();
+        if lines.next() != Some(SEGMENT_HEADER) {
+            return Err("header".into());
+        }
+
+        let mut doc: Option<IndexedDoc> = None;
+        for line in lines {
+            let invalid = || line.to_string();
+            match line.split_once('\t') {
+                Some(("doc", rest)) if doc.is_none() => {
+                    let (path, first_page) = rest.split_once('\t').ok_or_else(invalid)?;
+                    let first_page = first_page.parse().map_err(|_| invalid())?;
+                    doc = Some(IndexedDoc { path: PathBuf::from(unescape(path)), text: PdfText { first_page, pages: vec![] } });
+                },
+                Some(("page", text)) => {
+                    doc.as_mut().ok_or_else(invalid)?.text.pages.push(unescape(text));
+                },
+                Some(("term", rest)) => {
+                    let pages = doc.as_ref().ok_or_else(invalid)?.text.pages.len();
+                    let (term, postings) = rest.split_once('\t').ok_or_else(invalid)?;
+                    let entry = self.postings.entry(unescape(term)).or_default();
+                    for posting in postings.split(';') {
+                        let (page, positions) = posting.split_once(':').ok_or_else(invalid)?;
+                        let page: usize = page.parse().ok().filter(|page| *page < pages).ok_or_else(invalid)?;
+                        let positions = positions.split(',').map(|p| p.parse().map_err(|_| invalid())).collect::<Result<_, _>>()?;
+                        entry.insert((id, page), positions);
+                    }
+                },
+                _ => return Err(invalid()),
+            }
+        }
+        let doc = doc.ok_or("missing doc line")?;
+        self.docs.insert(id, doc);
+        Ok(())
+    }
+}
+
+fn segment_path(folder: &Path, id: u32) -> PathBuf {
+    folder.join(format!("{}.{}", id, SEGMENT_EXTENSION))
+}
+
+//The segment files of the folder with their document id
+fn segment_files(folder: &Path) -> io::Result<Vec<(PathBuf, u32)>> {
+    let mut files = vec![];
+    for entry in folder.read_dir()? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == SEGMENT_EXTENSION) {
+            if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse().ok()) {
+                files.push((path, id));
+            }
+        }
+    }
+    files.sort_by_key(|(_, id)| *id);
+    Ok(files)
+}
+
+//Lower cased words with their byte offset
+fn tokenize(text: &str) -> Vec<(String, usize)> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                tokens.push((text[s..i].to_lowercase(), s));
+                start = None;
+            },
+            _ => (),
+        }
+    }
+    tokens
+}
+
+fn snippet(page: &str, terms: &[String]) -> String {
+    let offset = tokenize(page).into_iter()
+        .find(|(token, _)| terms.contains(token))
+        .map(|(_, offset)| offset)
+        .unwrap_or(0);
+
+    let before: String = page[..offset].chars().rev().take(SNIPPET_CHARS).collect::<Vec<_>>().into_iter().rev().collect();
+    let after: String = page[offset..].chars().take(SNIPPET_CHARS * 2).collect();
+    format!("{}{}", before, after).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t").replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            (c, false) => out.push(c),
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Query {
+    Term(String),
+    Phrase(Vec<String>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl Query {
+    fn parse(query: &str) -> Result<Self, PdfError> {
+        let tokens = Self::lex(query)?;
+        let mut pos = 0;
+        let parsed = Self::parse_or(&tokens, &mut pos)?;
+        if pos < tokens.len() {
+            return Err(query_error(format!("unexpected {:?}", tokens[pos])));
+        }
+        Ok(parsed)
+    }
+
+    fn lex(query: &str) -> Result<Vec<Token>, PdfError> {
+        let mut tokens = vec![];
+        let mut chars = query.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            match c {
+                c if c.is_whitespace() => { chars.next(); },
+                '(' => { chars.next(); tokens.push(Token::Open); },
+                ')' => { chars.next(); tokens.push(Token::Close); },
+                '-' => { chars.next(); tokens.push(Token::Not); },
+                '"' => {
+                    chars.next();
+                    let mut phrase = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some(c) => phrase.push(c),
+                            None => return Err(query_error(format!("missing closing \" after {:?}", phrase))),
+                        }
+                    }
+                    tokens.push(Token::Phrase(phrase));
+                },
+                _ => {
+                    let mut word = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                            break;
+                        }
+                        word.push(c);
+                        chars.next();
+                    }
+                    tokens.push(match word.as_str() {
+                        "AND" => Token::And,
+                        "OR" => Token::Or,
+                        "NOT" => Token::Not,
+                        _ => Token::Word(word),
+                    });
+                },
+            }
+        }
+        if tokens.is_empty() {
+            return Err(query_error("empty query".into()));
+        }
+        Ok(tokens)
+    }
+
+    fn parse_or(tokens: &[Token], pos: &mut usize) -> Result<Self, PdfError> {
+        let mut left = Self::parse_and(tokens, pos)?;
+        while tokens.get(*pos) == Some(&Token::Or) {
+            *pos += 1;
+            left = Query::Or(Box::new(left), Box::new(Self::parse_and(tokens, pos)?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(tokens: &[Token], pos: &mut usize) -> Result<Self, PdfError> {
+        let mut left = Self::parse_not(tokens, pos)?;
+        loop {
+            match tokens.get(*pos) {
+                Some(Token::And) => *pos += 1,
+                Some(Token::Word(_) | Token::Phrase(_) | Token::Not | Token::Open) => (),
+                _ => return Ok(left),
+            }
+            left = Query::And(Box::new(left), Box::new(Self::parse_not(tokens, pos)?));
+        }
+    }
+
+    fn parse_not(tokens: &[Token], pos: &mut usize) -> Result<Self, PdfError> {
+        if tokens.get(*pos) == Some(&Token::Not) {
+            *pos += 1;
+            return Ok(Query::Not(Box::new(Self::parse_not(tokens, pos)?)));
+        }
+        Self::parse_primary(tokens, pos)
+    }
+
+    fn parse_primary(tokens: &[Token], pos: &mut usize) -> Result<Self, PdfError> {
+        let token = tokens.get(*pos).ok_or_else(|| query_error("query ends unexpectedly".into()))?;
+        *pos += 1;
+        match token {
+            Token::Open => {
+                let inner = Self::parse_or(tokens, pos)?;
+                if tokens.get(*pos) != Some(&Token::Close) {
+                    return Err(query_error("missing )".into()));
+                }
+                *pos += 1;
+                Ok(inner)
+            },
+            Token::Word(word) => Self::words(word),
+            Token::Phrase(phrase) => Self::words(phrase),
+            other => Err(query_error(format!("unexpected {:?}", other))),
+        }
+    }
+
+    //A word like "e-mail" is tokenized into several terms and searched as phrase
+    fn words(text: &str) -> Result<Self, PdfError> {
+        let mut terms: Vec<String> = tokenize(text).into_iter().map(|(term, _)| term).collect();
+        match terms.len() {
+            0 => Err(query_error(format!("no searchable word in {:?}", text))),
+            1 => Ok(Query::Term(terms.remove(0))),
+            _ => Ok(Query::Phrase(terms)),
+        }
+    }
+
+    fn positive_terms(&self) -> Vec<String> {
+        match self {
+            Query::Term(term) => vec![term.clone()],
+            Query::Phrase(terms) => terms.clone(),
+            Query::And(left, right) | Query::Or(left, right) => {
+                let mut terms = left.positive_terms();
+                terms.extend(right.positive_terms());
+                terms
+            },
+            Query::Not(_) => vec![],
+        }
+    }
+}
+
+fn query_error(message: String) -> PdfError {
+    PdfError::new(PdfErrorKind::InvalidQuery, format!("Invalid search query: {}", message), "".into())
+}
+
+#[test]
+fn test_search_index() {
+    let mut index = SearchIndex::new();
+    index.add_text(Path::new("contract.pdf"), PdfText { first_page: 1, pages: vec![
+        "This agreement is governed by the laws of Germany.".into(),
+        "Termination requires written notice.\nThe notice period is three months.".into(),
+    ]});
+    index.add_text(Path::new("invoice.pdf"), PdfText { first_page: 1, pages: vec![
+        "Invoice total: 100 EUR. Payment notice within 30 days.".into(),
+    ]});
+
+    let pages = |index: &SearchIndex, query: &str| -> Vec<(String, usize)> {
+        index.search(query).unwrap().into_iter().map(|h| (h.path.display().to_string(), h.page)).collect()
+    };
+
+    assert_eq!(pages(&index, "notice"), vec![("contract.pdf".into(), 2), ("invoice.pdf".into(), 1)]);
+    assert_eq!(pages(&index, "\"written notice\""), vec![("contract.pdf".into(), 2)]);
+    assert_eq!(pages(&index, "\"notice written\""), vec![]);
+    assert_eq!(pages(&index, "notice AND payment"), vec![("invoice.pdf".into(), 1)]);
+    assert_eq!(pages(&index, "notice -payment"), vec![("contract.pdf".into(), 2)]);
+    assert_eq!(pages(&index, "germany OR (invoice NOT months)"), vec![("contract.pdf".into(), 1), ("invoice.pdf".into(), 1)]);
+    assert_eq!(index.search("notice period").unwrap()[0].snippet, "Termination requires written notice. The notice period is three months.");
+    assert_eq!(index.search("(notice").unwrap_err().kind(), PdfErrorKind::InvalidQuery);
+
+    assert!(index.remove(Path::new("invoice.pdf")));
+    assert_eq!(pages(&index, "notice"), vec![("contract.pdf".into(), 2)]);
+    assert!(!index.remove(Path::new("invoice.pdf")));
+}
+
+#[test]
+fn test_search_index_save_load() {
+    let mut index = SearchIndex::new();
+    index.add_text(Path::new("a\tb.pdf"), PdfText { first_page: 3, pages: vec!["first\\line\nsecond\tline".into(), "other".into()] });
+    index.add_text(Path::new("c.pdf"), PdfText { first_page: 1, pages: vec!["second line".into()] });
+
+    let folder = std::env::temp_dir().join(format!("xpdf_search_{}", std::process::id()));
+    index.save(&folder).unwrap();
+    let mut loaded = SearchIndex::load(&folder).unwrap();
+    assert_eq!(loaded.docs, index.docs);
+    assert_eq!(loaded.postings, index.postings);
+    assert_eq!(loaded.search("\"second line\"").unwrap().iter().map(|h| h.page).collect::<Vec<_>>(), vec![3, 1]);
+    assert_eq!(loaded.search("other").unwrap()[0].page, 4);
+
+    //saving again only touches the changed documents
+    let untouched = std::time::SystemTime::UNIX_EPOCH;
+    fs::File::options().write(true).open(segment_path(&folder, 0)).unwrap().set_modified(untouched).unwrap();
+    loaded.remove(Path::new("c.pdf"));
+    loaded.add_text(Path::new("d.pdf"), PdfText { first_page: 1, pages: vec!["new".into()] });
+    loaded.save(&folder).unwrap();
+    let files: Vec<_> = segment_files(&folder).unwrap().into_iter().map(|(_, id)| id).collect();
+    let modified = fs::metadata(segment_path(&folder, 0)).unwrap().modified().unwrap();
+    let reloaded = SearchIndex::load(&folder).unwrap();
+    fs::remove_dir_all(&folder).unwrap();
+
+    assert_eq!(files, vec![0, 2]);
+    assert_eq!(modified, untouched);
+    assert_eq!(reloaded.documents().collect::<Vec<_>>(), vec![Path::new("a\tb.pdf"), Path::new("d.pdf")]);
+    assert_eq!(reloaded.postings, loaded.postings);
+}
+
+#[test]
+fn test_search_query_errors() {
+    let index = SearchIndex::new();
+    for query in ["\"unclosed phrase", "(notice", "notice)", "", "OR", "\"\""] {
+        assert_eq!(index.search(query).unwrap_err().kind(), PdfErrorKind::InvalidQuery, "{}", query);
+    }
+}
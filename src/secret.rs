@@ -0,0 +1,77 @@
+use std::fmt;
+
+use zeroize::Zeroize;
+
+/// Printed instead of a secret value
+pub const REDACTED: &str = "***";
+
+/// A password or other value which must not end up in logs
+/// Debug and Display print *** instead of the value, the memory is zeroed on drop.
+/// Only `expose` gives the value, e.g. to pass it to a tool.
+/// Copies made from it are plain strings and not zeroed: `XpdfArgs::to_str`/`to_osstr`,
+/// the argv of a tool run and the invocations recorded by `FakeRunner`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Secret::new(value)
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", REDACTED)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", REDACTED)
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+//Serialized as the plain value, so stored arguments can be read back
+#[cfg(feature = "serde")]
+impl serde::Serialize for Secret {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Secret {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret)
+    }
+}
+
+#[test]
+fn test_secret() {
+    let secret = Secret::from("hunter2");
+    assert_eq!(secret.expose(), "hunter2");
+    assert_eq!(format!("{:?} {}", secret, secret), "*** ***");
+    assert_eq!(format!("{:?}", Some(secret.clone())), "Some(***)");
+}
@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use crate::csv;
+use crate::encoding;
+use crate::password::PdfPassword;
+use crate::types::XpdfArgs;
+use crate::xpdf_text::{self, PdfText};
+use crate::{PdfError, XpdfTools};
+
+//Spaces between two words of a cell, wider runs separate fields
+const MIN_GAP: usize = 2;
+
+/// A table found in column aligned text, every row has the same number of cells
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Table {
+    pub page: usize,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn to_csv(&self) -> String {
+        self.rows.iter()
+            .map(|row| csv::row(row) + "\n")
+            .collect()
+    }
+}
+
+/// Finds the tables in text extracted with -table or -layout
+/// A table is a block of consecutive lines with at least two lines of several fields.
+/// Columns are separated where every line of the block has at least two spaces at the same position,
+/// lines of a single field inside the block (like wrapped descriptions) become rows with empty cells
+pub fn detect_tables(text: &PdfText) -> Vec<Table> {
+    let mut tables = vec![];
+    for (page, page_text) in text.numbered_pages() {
+        let lines: Vec<Vec<char>> = page_text.lines().map(|line| line.trim_end().chars().collect()).collect();
+        for block in lines.split(|line| line.is_empty()) {
+            let multi_field: Vec<usize> = (0..block.len()).filter(|i| field_count(&block[*i]) > 1).collect();
+            let (Some(first), Some(last)) = (multi_field.first(), multi_field.last()) else {
+                continue;
+            };
+            if multi_field.len() < 2 {
+                continue;
+            }
+            let block = &block[*first..=*last];
+            let columns = column_spans(block);
+            if columns.len() < 2 {
+                continue;
+            }
+            let rows = block.iter().map(|line| {
+                columns.iter().map(|(start, end)| {
+                    let end = (*end).min(line.len());
+                    let start = (*start).min(end);
+                    line[start..end].iter().collect::<String>().trim().to_string()
+                }).collect()
+            }).collect();
+            tables.push(Table { page, rows });
+        }
+    }
+    tables
+}
+
+/// All tables as CSV, each table preceded by a "# page <n>" line and followed by an empty line
+pub fn tables_to_csv(tables: &[Table]) -> String {
+    tables.iter().map(|table| format!("# page {}\n{}\n", table.page, table.to_csv())).collect()
+}
+
+fn field_count(line: &[char]) -> usize {
+    let text: String = line.iter().collect();
+    text.trim().split("  ").filter(|field| !field.trim().is_empty()).count()
+}
+
+//Start and end of the columns, the positions between are blank in every line
+fn column_spans(lines: &[Vec<char>]) -> Vec<(usize, usize)> {
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let blank: Vec<bool> = (0..width)
+        .map(|i| lines.iter().all(|line| line.get(i).is_none_or(|c| c.is_whitespace())))
+        .collect();
+
+    let mut spans = vec![];
+    let mut start = None;
+    let mut gap = 0;
+    for (i, is_blank) in blank.iter().enumerate() {
+        match (is_blank, start) {
+            (false, None) => start = Some(i),
+            (false, Some(_)) => gap = 0,
+            (true, Some(s)) => {
+                gap += 1;
+                if gap == MIN_GAP {
+                    spans.push((s, i + 1 - MIN_GAP));
+                    start = None;
+                    gap = 0;
+                }
+            },
+            (true, None) => (),
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, width));
+    }
+    spans
+}
+
+/// Runs pdftotext, with -layout unless -table or -layout is set, and detects the tables
+pub fn pdf_tables_with_password(pdf_file: &Path, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<Vec<Table>, PdfError> {
+    let args = tools.extra_args.as_deref().unwrap_or_default();
+    let options = match args.iter().any(|arg| matches!(arg, XpdfArgs::Table | XpdfArgs::Layout)) {
+        true => vec![],
+        false => vec![XpdfArgs::Layout.to_str()],
+    };
+    let raw = xpdf_text::pdf_to_binary_with_options(pdf_file, tools, password, &options)?;
+    let text = encoding::decode_output(&raw, &tools.output_encoding("pdftotext"))?;
+    Ok(detect_tables(&PdfText::parse(&text, args)))
+}
+
+#[test]
+fn test_detect_tables() {
+    let statement = "\
+Account statement  March 2024
+
+Date        Description              Amount    Balance
+03/01       Opening balance                    1,200.00
+03/04       Grocery Store             -54.20   1,145.80
+03/09       Transfer to savings
+            account 1234             -500.00     645.80
+
+Thank you for banking with us.
+\x0c\
+Item      Price
+Apple      0.50
+Pear       0.75
+\x0c";
+    let tables = detect_tables(&PdfText::parse(statement, &[]));
+
+    assert_eq!(tables.len(), 2);
+    assert_eq!(tables[0].page, 1);
+    assert_eq!(tables[0].rows[0], vec!["Date", "Description", "Amount", "Balance"]);
+    assert_eq!(tables[0].rows[1], vec!["03/01", "Opening balance", "", "1,200.00"]);
+    assert_eq!(tables[0].rows[3], vec!["03/09", "Transfer to savings", "", ""]);
+    assert_eq!(tables[0].rows[4], vec!["", "account 1234", "-500.00", "645.80"]);
+    assert_eq!(tables[1].page, 2);
+    assert_eq!(tables[1].to_csv(), "Item,Price\nApple,0.50\nPear,0.75\n");
+    assert!(tables_to_csv(&tables).contains("\n# page 2\nItem,Price\n"));
+
+    let amounts = Table { page: 1, rows: vec![vec!["total".into(), "1,200.00".into()]] };
+    assert_eq!(amounts.to_csv(), "total,\"1,200.00\"\n");
+}
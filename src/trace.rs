@@ -0,0 +1,200 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::limits::OutputLimit;
+use crate::runner::{CountingWriter, ToolOutput, ToolRunner};
+#[cfg(feature = "tracing")]
+use crate::PdfError;
+
+/// Runs the tool through the runner in an `xpdf_process` span
+/// The span has the tool, the pdf file, the arguments with passwords redacted,
+/// the duration, the exit status, the stdout and stderr byte counts and whether the output was truncated.
+/// Without the `tracing` feature this only runs the tool.
+#[cfg(feature = "tracing")]
+pub(crate) fn run(runner: &dyn ToolRunner, program: &Path, file: Option<&Path>, args: &[String], limit: Option<&OutputLimit>) -> io::Result<ToolOutput> {
+    process_span(program, file, args, || run_tool(runner, program, args, limit).map(|o| (o, 0))).map(|(o, _)| o)
+}
+
+/// Like run, the stdout of the tool goes into the writer, returns the output and the number of bytes written
+#[cfg(feature = "tracing")]
+pub(crate) fn run_streamed(runner: &dyn ToolRunner, program: &Path, file: Option<&Path>, args: &[String], limit: Option<&OutputLimit>, out: &mut dyn Write) -> io::Result<(ToolOutput, u64)> {
+    process_span(program, file, args, || stream_tool(runner, program, args, limit, out))
+}
+
+//The run gives the output and the bytes streamed past it, both count as stdout_bytes
+#[cfg(feature = "tracing")]
+fn process_span(program: &Path, file: Option<&Path>, args: &[String], run: impl FnOnce() -> io::Result<(ToolOutput, u64)>) -> io::Result<(ToolOutput, u64)> {
+    use tracing::field::{display, Empty};
+
+    let tool = program.file_stem().unwrap_or_default().to_string_lossy();
+    let span = tracing::info_span!("xpdf_process",
+        tool = %tool,
+        file = Empty,
+        args = %crate::redact_args(args).join(" "),
+        duration_ms = Empty,
+        status = Empty,
+        stdout_bytes = Empty,
+        stderr_bytes = Empty,
+        truncated = Empty,
+        error = Empty,
+    );
+    if let Some(file) = file {
+        span.record("file", display(file.display()));
+    }
+
+    let start = std::time::Instant::now();
+    let output = span.in_scope(run);
+    span.record("duration_ms", start.elapsed().as_millis() as u64);
+    match &output {
+        Ok((o, streamed)) => {
+            //a process killed by a signal has no exit status
+            if let Some(status) = o.status {
+                span.record("status", status);
+            }
+            span.record("stdout_bytes", o.stdout.len() as u64 + streamed);
+            span.record("stderr_bytes", o.stderr.len() as u64);
+            span.record("truncated", o.truncated);
+        },
+        Err(e) => {
+            span.record("error", display(e));
+        },
+    }
+    output
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn run(runner: &dyn ToolRunner, program: &Path, _file: Option<&Path>, args: &[String], limit: Option<&OutputLimit>) -> io::Result<ToolOutput> {
+    run_tool(runner, program, args, limit)
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn run_streamed(runner: &dyn ToolRunner, program: &Path, _file: Option<&Path>, args: &[String], limit: Option<&OutputLimit>, out: &mut dyn Write) -> io::Result<(ToolOutput, u64)> {
+    stream_tool(runner, program, args, limit, out)
+}
+
+fn stream_tool(runner: &dyn ToolRunner, program: &Path, args: &[String], limit: Option<&OutputLimit>, out: &mut dyn Write) -> io::Result<(ToolOutput, u64)> {
+    let mut counted = CountingWriter { inner: out, count: 0 };
+    let output = runner.run_streamed(program, args, limit, &mut counted)?;
+    Ok((output, counted.count))
+}
+
+fn run_tool(runner: &dyn ToolRunner, program: &Path, args: &[String], limit: Option<&OutputLimit>) -> io::Result<ToolOutput> {
+    match limit {
+        Some(limit) => runner.run_limited(program, args, limit),
+        None => runner.run(program, args),
+    }
+}
+
+/// A warning for output bytes the encoding could not decode, they were replaced by U+FFFD
+#[cfg(feature = "tracing")]
+pub(crate) fn replaced(encoding: &str, count: usize) {
+    tracing::warn!(encoding, replaced = count, "undecodable bytes in the tool output replaced");
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn replaced(_encoding: &str, _count: usize) {}
+
+/// Runs a call on the file in an `xpdf_call` span, the parent of its process spans
+/// The outcome is "ok" or the PdfErrorKind of the failure, including errors parsing the tool output.
+#[cfg(feature = "tracing")]
+pub(crate) fn call<T>(file: &Path, run: impl FnOnce() -> Result<T, PdfError>) -> Result<T, PdfError> {
+    let span = tracing::info_span!("xpdf_call", file = %file.display(), outcome = tracing::field::Empty);
+    let result = span.in_scope(run);
+    match &result {
+        Ok(_) => span.record("outcome", "ok"),
+        Err(e) => span.record("outcome", tracing::field::debug(e.kind())),
+    };
+    result
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn call<T, E>(_file: &Path, run: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    run()
+}
+
+//Collects the name and fields of every span
+#[cfg(all(test, feature = "tracing"))]
+mod recorder {
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    pub type Spans = Vec<(String, BTreeMap<String, String>)>;
+
+    #[derive(Clone, Default)]
+    pub struct Recorder(pub Arc<Mutex<Spans>>);
+
+    struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name().into(), format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().into(), value.into());
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = BTreeMap::new();
+            span.record(&mut Fields(&mut fields));
+            spans.push((span.metadata().name().into(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.0.lock().unwrap();
+            values.record(&mut Fields(&mut spans[span.into_u64() as usize - 1].1));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tool_spans() {
+    use std::path::PathBuf;
+    use crate::runner::FakeRunner;
+    use crate::types::XpdfArgs;
+    use crate::XpdfTools;
+
+    let runner = FakeRunner::new()
+        .with_output("pdfinfo", ToolOutput::ok("pdfinfo version 4.04\n"))
+        .with_output("pdfinfo", ToolOutput::ok("Pages:          2\n"));
+    let tools = XpdfTools::builder_unchecked(PathBuf::from("/opt/xpdf-tracing"), &["pdfinfo"])
+        .runner(runner)
+        .extra_args(vec![XpdfArgs::OwnerPassword("secret".into())])
+        .build().unwrap();
+
+    let recorder = recorder::Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || tools.pdf_info(Path::new("a.pdf")).unwrap());
+
+    let spans = recorder.0.lock().unwrap();
+    let names: Vec<_> = spans.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["xpdf_call", "xpdf_process"]);
+    assert_eq!(spans[0].1["outcome"], "ok");
+
+    let process = &spans[1].1;
+    assert_eq!(process["tool"], "pdfinfo");
+    assert_eq!(process["file"], "a.pdf");
+    assert_eq!(process["args"], "-opw *** a.pdf");
+    assert_eq!(process["status"], "0");
+    assert_eq!(process["stdout_bytes"], "18");
+    assert_eq!(process["stderr_bytes"], "0");
+    assert!(process.contains_key("duration_ms"));
+}
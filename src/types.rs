@@ -5,10 +5,14 @@ use std::ops::{Deref, DerefMut};
 use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+use crate::secret::{Secret, REDACTED};
+use crate::version::ToolBackend;
+
 
 
 
 #[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct XpdfInfoMap(pub BTreeMap<String, Option<String>>);
 
 impl Deref for XpdfInfoMap {
@@ -29,7 +33,7 @@ impl fmt::Debug for XpdfInfoMap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
        
         for (key, value) in &self.0 {
-            let value = value.as_deref().unwrap_or("None".into());
+            let value = value.as_deref().unwrap_or("None");
             writeln!(f, "{key} - {value}")?;
         }
 
@@ -69,6 +73,7 @@ impl XpdfInfoMap {
 
 /// Enumerates all possible arguments for the XpdfTools process arguments
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum XpdfArgs {
     /// valid for xpdfinfo, xpdftext; Specifies the first page to examine.
     FirstPage(usize), 
@@ -85,9 +90,9 @@ pub enum XpdfArgs {
     // valid for xpdfinfo, xpdftext
     Encoding(String),
     // valid for xpdfinfo, xpdftext
-    OwnerPassword(String),
+    OwnerPassword(Secret),
     // valid for xpdfinfo, xpdftext
-    UserPassword(String),
+    UserPassword(Secret),
     // valid for xpdfinfo, xpdftext
     Config(PathBuf),
     // valid for xpdfinfo, xpdtext
@@ -134,9 +139,14 @@ pub enum XpdfArgs {
     Listencodings
 }
 
+//Passwords are redacted, to_str gives the argument passed to the tool
 impl fmt::Display for XpdfArgs {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_osstr().to_string_lossy())
+        match self {
+            XpdfArgs::OwnerPassword(_) => write!(f, "-opw {}", REDACTED),
+            XpdfArgs::UserPassword(_) => write!(f, "-upw {}", REDACTED),
+            _ => write!(f, "{}", self.to_osstr().to_string_lossy()),
+        }
     }
 }
 
@@ -147,17 +157,45 @@ impl XpdfArgs {
     pub fn to_str(&self) -> String {
         self.to_osstr().to_str().unwrap().to_owned()
     }
+
+    /// The argument as understood by the given backend, None if the backend has no equivalent
+    pub fn to_str_for(&self, backend: ToolBackend) -> Option<String> {
+        match backend {
+            ToolBackend::Xpdf => Some(self.to_str()),
+            ToolBackend::Poppler => match self {
+                XpdfArgs::Listencodings => Some("-listenc".into()),
+                XpdfArgs::Simple | XpdfArgs::Simple2 | XpdfArgs::Table | XpdfArgs::Lineprinter |
+                XpdfArgs::Linespacing(_) | XpdfArgs::Clip | XpdfArgs::Bom | XpdfArgs::Verbose | XpdfArgs::Config(_) |
+                XpdfArgs::MarginLeft(_) | XpdfArgs::MarginRight(_) | XpdfArgs::MarginTop(_) | XpdfArgs::MarginBottom(_) => None,
+                _ => Some(self.to_str()),
+            },
+        }
+    }
+
+    /// The argument as argv entries for the backend, a value is its own entry and is never split on spaces
+    pub fn to_args_for(&self, backend: ToolBackend) -> Option<Vec<String>> {
+        let value = match self {
+            XpdfArgs::OwnerPassword(pwd) => return Some(vec!["-opw".into(), pwd.expose().into()]),
+            XpdfArgs::UserPassword(pwd) => return Some(vec!["-upw".into(), pwd.expose().into()]),
+            XpdfArgs::Encoding(value) | XpdfArgs::Eol(value) => value.clone(),
+            XpdfArgs::Config(config_file) => config_file.display().to_string(),
+            _ => return self.to_str_for(backend).map(|arg| arg.split(' ').map(|s| s.to_owned()).collect()),
+        };
+        let flag = self.to_str_for(backend)?.split(' ').next()?.to_owned();
+        Some(vec![flag, value])
+    }
+
     pub fn to_osstr(&self) -> OsString {
         match self {
             XpdfArgs::FirstPage(number) => format!("-f {}", number).into(),
-            XpdfArgs::LastPage(number) => format!("-f {}", number).into(),
+            XpdfArgs::LastPage(number) => format!("-l {}", number).into(),
             XpdfArgs::Box => OsString::from("-box"),//.to_string(),
             XpdfArgs::RawDates => OsString::from("-rawdates"),
             XpdfArgs::Metadata => OsString::from("-meta"),
             XpdfArgs::Custom => OsString::from("-custom"),
             XpdfArgs::Encoding(encoding_name) => format!("-enc {}", encoding_name).into(),
-            XpdfArgs::OwnerPassword(pwd) => format!("-opw {}", pwd).into(),
-            XpdfArgs::UserPassword(pwd) => format!("-upw {}", pwd).into(),
+            XpdfArgs::OwnerPassword(pwd) => format!("-opw {}", pwd.expose()).into(),
+            XpdfArgs::UserPassword(pwd) => format!("-upw {}", pwd.expose()).into(),
             XpdfArgs::Config(config_file) => format!("-cfg {}", config_file.display()).into(),
             XpdfArgs::Version => OsString::from("-v"),
             XpdfArgs::Layout => OsString::from("-layout"),
@@ -184,13 +222,39 @@ impl XpdfArgs {
         }
     }
 
+    /// The xpdf version which introduced the argument, None if it is available since 3.x
+    pub fn min_xpdf_version(&self) -> Option<(u32, u32)> {
+        match self {
+            XpdfArgs::Custom | XpdfArgs::Simple | XpdfArgs::Table |
+            XpdfArgs::NoDiag | XpdfArgs::Bom | XpdfArgs::Verbose | XpdfArgs::Listencodings |
+            XpdfArgs::MarginLeft(_) | XpdfArgs::MarginRight(_) | XpdfArgs::MarginTop(_) | XpdfArgs::MarginBottom(_) => Some((4, 0)),
+            XpdfArgs::Simple2 => Some((4, 2)),
+            _ => None,
+        }
+    }
+
     pub fn is_valid_for(&self, tool: &str) -> bool {
         match tool {
             "pdfinfo" => self.is_valid_info_arg(),
             "pdftotext" => self.is_valid_totext_arg(),
+            "pdffonts" | "pdfimages" | "pdftopng" | "pdftoppm" => self.is_valid_page_tool_arg(),
+            "pdfdetach" => self.is_valid_detach_arg(),
             _ => false,
         }
     }
+
+    /// valid for the tools working on a page range: pdffonts, pdfimages, pdftopng, pdftoppm
+    pub fn is_valid_page_tool_arg(&self) -> bool {
+        matches!(self,
+            XpdfArgs::FirstPage(_) | XpdfArgs::LastPage(_) | XpdfArgs::Quit |
+            XpdfArgs::OwnerPassword(_) | XpdfArgs::UserPassword(_) | XpdfArgs::Config(_))
+    }
+
+    pub fn is_valid_detach_arg(&self) -> bool {
+        matches!(self,
+            XpdfArgs::Encoding(_) | XpdfArgs::Quit |
+            XpdfArgs::OwnerPassword(_) | XpdfArgs::UserPassword(_) | XpdfArgs::Config(_))
+    }
     pub fn is_valid_info_arg(&self) -> bool {
         match self {
             XpdfArgs::FirstPage(_) | XpdfArgs::LastPage(_) | 
@@ -219,6 +283,14 @@ impl XpdfArgs {
     
 }
 
+#[test]
+fn test_poppler_args() {
+    assert_eq!(XpdfArgs::Listencodings.to_str_for(ToolBackend::Poppler), Some("-listenc".into()));
+    assert_eq!(XpdfArgs::Listencodings.to_str_for(ToolBackend::Xpdf), Some("-listencodings".into()));
+    assert_eq!(XpdfArgs::Table.to_str_for(ToolBackend::Poppler), None);
+    assert_eq!(XpdfArgs::Layout.to_str_for(ToolBackend::Poppler), Some("-layout".into()));
+}
+
 #[ignore]
 #[test]
 fn test_xpdf_map() {
@@ -233,7 +305,7 @@ fn test_xpdf_map() {
 
 #[test]
 fn test_arguments() {
-    let args = vec![XpdfArgs::FirstPage(1), XpdfArgs::Metadata, XpdfArgs::MarginBottom(10)];
+    let args = [XpdfArgs::FirstPage(1), XpdfArgs::Metadata, XpdfArgs::MarginBottom(10)];
     let info_args:Vec<_> = args.iter().filter(|&arg| arg.is_valid_info_arg()).collect();
     let text_args:Vec<_> = args.iter().filter(|&arg| arg.is_valid_totext_arg()).collect();
 
@@ -242,3 +314,28 @@ fn test_arguments() {
     
     
 }
+
+#[test]
+fn test_page_range_arguments() {
+    assert_eq!(XpdfArgs::FirstPage(2).to_str(), "-f 2");
+    assert_eq!(XpdfArgs::LastPage(3).to_str(), "-l 3");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_roundtrip() {
+    let args = vec![XpdfArgs::FirstPage(2), XpdfArgs::Layout, XpdfArgs::Encoding("UTF-8".into())];
+    let json = serde_json::to_string(&args).unwrap();
+    assert_eq!(json, r#"[{"FirstPage":2},"Layout",{"Encoding":"UTF-8"}]"#);
+    assert_eq!(serde_json::from_str::<Vec<XpdfArgs>>(&json).unwrap(), args);
+
+    let mut map = XpdfInfoMap::create();
+    map.insert("Pages".into(), Some("3".into()));
+    let json = serde_json::to_string(&map).unwrap();
+    assert!(json.contains(r#""Pages":"3""#) && json.contains(r#""Title":null"#));
+    assert!(serde_json::from_str::<XpdfInfoMap>(&json).unwrap() == map);
+
+    let error = crate::PdfError::new(crate::PdfErrorKind::PasswordRequired, "m".into(), "p".into());
+    let json = serde_json::to_string(&error).unwrap();
+    assert_eq!(serde_json::from_str::<crate::PdfError>(&json).unwrap(), error);
+}
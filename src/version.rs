@@ -0,0 +1,123 @@
+use core::fmt;
+
+use std::path::Path;
+
+use crate::runner::{ProcessRunner, ToolRunner};
+use crate::types::XpdfArgs;
+
+/// The implementation behind a tool binary
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ToolBackend {
+    Xpdf,
+    Poppler,
+}
+
+/// Version and copyright banner reported by a tool with `-v`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ToolVersion {
+    pub backend: ToolBackend,
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// the version string as printed, e.g. "4.04"
+    pub version: String,
+    /// the copyright lines following the version line
+    pub copyright: Vec<String>,
+}
+
+impl fmt::Display for ToolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.backend {
+            ToolBackend::Xpdf => write!(f, "xpdf {}", self.version),
+            ToolBackend::Poppler => write!(f, "poppler {}", self.version),
+        }
+    }
+}
+
+impl ToolVersion {
+    /// Parses the output of `<tool> -v`
+    /// xpdf writes the banner to stderr, poppler to stdout or stderr depending on the version
+    pub fn parse(output: &str) -> Option<Self> {
+        let version_re = regex::Regex::new(r"(?m)^\S+ version (?<version>\d+(\.\d+)*)").unwrap();
+        let caps = version_re.captures(output)?;
+        let version = caps.name("version").unwrap().as_str().to_string();
+
+        let mut numbers = version.split('.').map(|n| n.parse::<u32>().unwrap_or(0));
+        let major = numbers.next().unwrap_or(0);
+        let minor = numbers.next().unwrap_or(0);
+        let patch = numbers.next().unwrap_or(0);
+
+        let copyright: Vec<String> = output.lines()
+            .map(|l| l.trim())
+            .filter(|l| l.starts_with("Copyright"))
+            .map(|l| l.to_string())
+            .collect();
+
+        let backend = if copyright.iter().any(|l| l.contains("Poppler")) {
+            ToolBackend::Poppler
+        } else {
+            ToolBackend::Xpdf
+        };
+
+        Some(ToolVersion { backend, major, minor, patch, version, copyright })
+    }
+
+    /// Runs `<tool> -v` and parses the banner
+    pub fn detect(tool_path: &Path) -> Option<Self> {
+        Self::detect_with(&ProcessRunner::new(), tool_path)
+    }
+
+    pub fn detect_with(runner: &dyn ToolRunner, tool_path: &Path) -> Option<Self> {
+        let output = crate::trace::run(runner, tool_path, None, &["-v".to_string()], None).ok()?;
+        let mut banner = String::from_utf8_lossy(&output.stdout).into_owned();
+        banner.push_str(&String::from_utf8_lossy(&output.stderr));
+        Self::parse(&banner)
+    }
+
+    pub fn at_least(&self, major: u32, minor: u32) -> bool {
+        (self.major, self.minor) >= (major, minor)
+    }
+
+    /// Whether the tool accepts the argument
+    /// Arguments introduced with a later xpdf release are rejected on older ones, poppler needs an equivalent flag
+    pub fn supports(&self, arg: &XpdfArgs) -> bool {
+        match self.backend {
+            ToolBackend::Xpdf => match arg.min_xpdf_version() {
+                Some((major, minor)) => self.at_least(major, minor),
+                None => true,
+            },
+            ToolBackend::Poppler => arg.to_str_for(ToolBackend::Poppler).is_some(),
+        }
+    }
+}
+
+#[test]
+fn test_parse_versions() {
+    let xpdf3 = ToolVersion::parse("pdftotext version 3.04\nCopyright 1996-2014 Glyph & Cog, LLC\n").unwrap();
+    assert_eq!(xpdf3.backend, ToolBackend::Xpdf);
+    assert_eq!((xpdf3.major, xpdf3.minor, xpdf3.patch), (3, 4, 0));
+    assert_eq!(xpdf3.copyright, vec!["Copyright 1996-2014 Glyph & Cog, LLC".to_string()]);
+    assert!(!xpdf3.supports(&XpdfArgs::Table));
+    assert!(!xpdf3.supports(&XpdfArgs::Simple2));
+    assert!(xpdf3.supports(&XpdfArgs::Layout));
+
+    let xpdf4 = ToolVersion::parse("pdftotext version 4.04 [www.xpdfreader.com]\nCopyright 1996-2022 Glyph & Cog, LLC\n").unwrap();
+    assert_eq!(xpdf4.to_string(), "xpdf 4.04");
+    assert!(xpdf4.supports(&XpdfArgs::Table));
+    assert!(xpdf4.supports(&XpdfArgs::Simple2));
+
+    //-simple2 came with 4.02
+    let xpdf400 = ToolVersion::parse("pdftotext version 4.00\nCopyright 1996-2017 Glyph & Cog, LLC\n").unwrap();
+    assert!(xpdf400.supports(&XpdfArgs::Table));
+    assert!(!xpdf400.supports(&XpdfArgs::Simple2));
+
+    let poppler = ToolVersion::parse("pdfinfo version 22.02.0\nCopyright 2005-2022 The Poppler Developers - http://poppler.freedesktop.org\nCopyright 1996-2011 Glyph & Cog, LLC\n").unwrap();
+    assert_eq!(poppler.backend, ToolBackend::Poppler);
+    assert_eq!((poppler.major, poppler.minor, poppler.patch), (22, 2, 0));
+    assert!(!poppler.supports(&XpdfArgs::Table));
+    assert!(poppler.supports(&XpdfArgs::Listencodings));
+
+    assert!(ToolVersion::parse("not a banner").is_none());
+}
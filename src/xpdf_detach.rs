@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+
+use crate::{encoding, limits::OutputFiles, password::PdfPassword, PdfError, PdfErrorKind, XpdfTools};
+
+/// An embedded file listed by `pdfdetach -list`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PdfAttachment {
+    /// 1 based index as used by `pdfdetach -save`
+    pub index: usize,
+    pub name: String,
+}
+
+impl PdfAttachment {
+    /// The name reduced to a single file name, folders in it are dropped: "../../x/notes.txt" becomes "notes.txt"
+    /// A name without a file name part becomes "attachment-<index>"
+    pub fn file_name(&self) -> String {
+        match self.name.rsplit(['/', '\\', ':']).next().unwrap_or("").trim() {
+            "" | "." | ".." => format!("attachment-{}", self.index),
+            name => name.to_string(),
+        }
+    }
+}
+
+/// Parses stored `pdfdetach -list` output
+pub fn parse_attachments(raw: &str) -> Vec<PdfAttachment> {
+    let line_re = regex::Regex::new(r"^\s*(?<index>\d+): (?<name>.+)$").unwrap();
+    raw.lines()
+        .filter_map(|line| line_re.captures(line))
+        .map(|caps| PdfAttachment {
+            index: caps["index"].parse().unwrap_or(0),
+            name: caps["name"].to_string(),
+        })
+        .collect()
+}
+
+pub fn pdf_attachments_with_password(pdf_file: &Path, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<Vec<PdfAttachment>, PdfError> {
+    let output = tools.run_pdf_tool("pdfdetach", pdf_file, password, &["-list".into()], &[], PdfErrorKind::PdfDetachError)?;
+    Ok(parse_attachments(&encoding::decode_output(&output.stdout, &tools.output_encoding("pdfdetach"))?))
+}
+
+/// Saves every embedded file into the folder, returns the written files
+/// Each file is saved on its own under `PdfAttachment::file_name`, a name from the pdf cannot point outside the folder
+pub fn pdf_attachments_save_with_password(pdf_file: &Path, out_dir: &Path, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<Vec<PathBuf>, PdfError> {
+    let attachments = pdf_attachments_with_password(pdf_file, tools, password)?;
+    let mut files = vec![];
+    for attachment in &attachments {
+        let file = out_dir.join(attachment.file_name());
+        let options = vec!["-save".into(), attachment.index.to_string(), "-o".into(), tools.path_arg(&file)];
+        tools.run_pdf_tool_to_files("pdfdetach", pdf_file, password, &options, &[], OutputFiles::dir(out_dir), PdfErrorKind::PdfDetachError)?;
+        files.push(file);
+    }
+    Ok(files)
+}
+
+#[test]
+fn test_parse_attachments() {
+    let attachments = parse_attachments("2 embedded files\n1: invoice.xml\n2: company logo.png\n");
+    assert_eq!(attachments, vec![
+        PdfAttachment { index: 1, name: "invoice.xml".into() },
+        PdfAttachment { index: 2, name: "company logo.png".into() },
+    ]);
+    assert!(parse_attachments("0 embedded files\n").is_empty());
+
+    let file_name = |name: &str| PdfAttachment { index: 3, name: name.into() }.file_name();
+    assert_eq!(file_name("invoice.xml"), "invoice.xml");
+    assert_eq!(file_name("../../etc/passwd"), "passwd");
+    assert_eq!(file_name("/etc/cron.d/job"), "job");
+    assert_eq!(file_name("C:\\Windows\\evil.dll"), "evil.dll");
+    assert_eq!(file_name(".."), "attachment-3");
+    assert_eq!(file_name("folder/"), "attachment-3");
+}
@@ -0,0 +1,118 @@
+use std::ops::Range;
+use std::path::Path;
+
+use crate::{encoding, password::PdfPassword, PdfError, PdfErrorKind, XpdfTools};
+
+/// A row of the pdffonts output
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PdfFont {
+    pub name: String,
+    pub font_type: String,
+    /// only printed by poppler
+    pub encoding: Option<String>,
+    pub embedded: bool,
+    pub subset: bool,
+    pub unicode: bool,
+    /// object number and generation
+    pub object_id: Option<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PdfFonts {
+    pub raw: String,
+    pub fonts: Vec<PdfFont>,
+}
+
+impl PdfFonts {
+    /// Parses stored pdffonts output
+    /// The column layout differs between xpdf 3.x, 4.x and poppler, so the columns are taken
+    /// from the header and the dashes below it
+    pub fn parse(raw: &str) -> Self {
+        let mut lines = raw.lines();
+        let mut fonts = vec![];
+
+        let header = lines.next().unwrap_or("");
+        let dashes = lines.next().unwrap_or("");
+        let columns = column_spans(dashes);
+        let names: Vec<String> = columns.iter().map(|span| cut(header, span)).collect();
+
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            let value = |name: &str| -> Option<String> {
+                names.iter().position(|n| n == name).map(|i| cut(line, &columns[i]))
+            };
+            let yes = |name: &str| value(name).is_some_and(|v| v == "yes");
+
+            let object_id = value("object ID").and_then(|id| {
+                let mut numbers = id.split_whitespace().map(|n| n.parse::<u32>().ok());
+                Some((numbers.next()??, numbers.next()??))
+            });
+
+            fonts.push(PdfFont {
+                name: value("name").unwrap_or_default(),
+                font_type: value("type").unwrap_or_default(),
+                encoding: value("encoding"),
+                embedded: yes("emb"),
+                subset: yes("sub"),
+                unicode: yes("uni"),
+                object_id,
+            });
+        }
+
+        PdfFonts { raw: raw.to_string(), fonts }
+    }
+}
+
+pub fn pdf_fonts(pdf_file: &Path, tools: &XpdfTools) -> Result<PdfFonts, PdfError> {
+    pdf_fonts_with_password(pdf_file, tools, None)
+}
+
+pub fn pdf_fonts_with_password(pdf_file: &Path, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<PdfFonts, PdfError> {
+    let output = tools.run_pdf_tool("pdffonts", pdf_file, password, &[], &[], PdfErrorKind::PdfFontsError)?;
+    Ok(PdfFonts::parse(&encoding::decode_output(&output.stdout, &tools.output_encoding("pdffonts"))?))
+}
+
+//Byte ranges of the dash groups, the last column extends to the line end
+fn column_spans(dashes: &str) -> Vec<Range<usize>> {
+    let mut spans: Vec<Range<usize>> = vec![];
+    let mut start = None;
+    for (i, c) in dashes.char_indices() {
+        match (c == '-', start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push(s..i);
+                start = None;
+            },
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        spans.push(s..usize::MAX);
+    }
+    spans
+}
+
+fn cut(line: &str, span: &Range<usize>) -> String {
+    let start = span.start.min(line.len());
+    let end = span.end.min(line.len());
+    line.get(start..end).unwrap_or("").trim().to_string()
+}
+
+#[test]
+fn test_fonts_fixtures() {
+    for file in crate::fixtures::layouts("pdffonts") {
+        let raw = std::fs::read_to_string(&file).unwrap();
+        let fonts = PdfFonts::parse(&raw).fonts;
+
+        assert_eq!(fonts.len(), 3, "{:?}", file);
+        assert_eq!(fonts[0].name, "BAAAAA+LiberationSerif");
+        assert_eq!(fonts[1].font_type, "Type 1");
+        assert!(!fonts[1].embedded);
+        assert_eq!(fonts[2].font_type, "CID TrueType");
+        assert!(fonts[2].embedded && fonts[2].subset && fonts[2].unicode);
+        assert_eq!(fonts[2].object_id, Some((15, 0)));
+        let poppler = file.file_name().unwrap().to_string_lossy().starts_with("poppler");
+        assert_eq!(fonts[0].encoding.is_some(), poppler, "{:?}", file);
+    }
+}
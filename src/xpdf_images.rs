@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+
+use crate::{encoding, limits::OutputFiles, password::PdfPassword, PdfError, PdfErrorKind, XpdfTools};
+
+/// A row of the `pdfimages -list` output
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PdfImage {
+    pub page: usize,
+    pub num: usize,
+    /// image, mask, smask or stencil
+    pub image_type: String,
+    pub width: u32,
+    pub height: u32,
+    pub color: String,
+    pub components: u32,
+    pub bits_per_component: u32,
+    pub encoding: String,
+    pub interpolate: bool,
+    /// object number and generation, None for inline images
+    pub object_id: Option<(u32, u32)>,
+    pub x_ppi: Option<u32>,
+    pub y_ppi: Option<u32>,
+    /// as printed, e.g. "85.3K"
+    pub size: String,
+    /// as printed, e.g. "3.6%"
+    pub ratio: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PdfImages {
+    pub raw: String,
+    pub images: Vec<PdfImage>,
+}
+
+impl PdfImages {
+    /// Parses stored `pdfimages -list` output (xpdf 4.x and poppler)
+    /// Values are looked up by the header names, inline images print "[inline]" as object ID
+    pub fn parse(raw: &str) -> Self {
+        let mut lines = raw.lines();
+        let header: Vec<&str> = lines.next().unwrap_or("").split_whitespace().collect();
+        let mut images = vec![];
+
+        for line in lines.filter(|l| !l.trim().is_empty() && !l.starts_with('-')) {
+            let mut values: Vec<&str> = line.split_whitespace().collect();
+            //"object ID" are two header words, an inline image has a single "[inline]" value
+            if let Some(i) = values.iter().position(|v| *v == "[inline]") {
+                values.insert(i + 1, "");
+            }
+            if values.len() != header.len() {
+                continue;
+            }
+
+            let value = |name: &str| header.iter().position(|h| *h == name).map(|i| values[i]).unwrap_or("");
+            let number = |name: &str| value(name).parse::<u32>().ok();
+
+            let object_id = match (value("object").parse::<u32>(), value("ID").parse::<u32>()) {
+                (Ok(number), Ok(generation)) => Some((number, generation)),
+                _ => None,
+            };
+
+            images.push(PdfImage {
+                page: value("page").parse().unwrap_or(0),
+                num: value("num").parse().unwrap_or(0),
+                image_type: value("type").into(),
+                width: number("width").unwrap_or(0),
+                height: number("height").unwrap_or(0),
+                color: value("color").into(),
+                components: number("comp").unwrap_or(0),
+                bits_per_component: number("bpc").unwrap_or(0),
+                encoding: value("enc").into(),
+                interpolate: value("interp") == "yes",
+                object_id,
+                x_ppi: number("x-ppi"),
+                y_ppi: number("y-ppi"),
+                size: value("size").into(),
+                ratio: value("ratio").into(),
+            });
+        }
+
+        PdfImages { raw: raw.to_string(), images }
+    }
+}
+
+pub fn pdf_images(pdf_file: &Path, tools: &XpdfTools) -> Result<PdfImages, PdfError> {
+    pdf_images_with_password(pdf_file, tools, None)
+}
+
+pub fn pdf_images_with_password(pdf_file: &Path, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<PdfImages, PdfError> {
+    let output = tools.run_pdf_tool("pdfimages", pdf_file, password, &["-list".into()], &[], PdfErrorKind::PdfImagesError)?;
+    Ok(PdfImages::parse(&encoding::decode_output(&output.stdout, &tools.output_encoding("pdfimages"))?))
+}
+
+/// Writes the images into the folder, named after the pdf file, jpeg images are kept as .jpg
+/// Returns the image files in the folder starting with the pdf file name
+pub fn pdf_images_extract_with_password(pdf_file: &Path, out_dir: &Path, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<Vec<PathBuf>, PdfError> {
+    let root = crate::output_root(pdf_file, out_dir);
+    let before = crate::files_with_root(&root);
+    tools.run_pdf_tool_to_files("pdfimages", pdf_file, password, &["-j".into()], &[tools.path_arg(&root)], OutputFiles::root(&root), PdfErrorKind::PdfImagesError)?;
+    Ok(crate::written_files(&root, &before))
+}
+
+#[test]
+fn test_images_fixtures() {
+    for file in crate::fixtures::layouts("pdfimages") {
+        let raw = std::fs::read_to_string(&file).unwrap();
+        let images = PdfImages::parse(&raw).images;
+
+        assert_eq!(images.len(), 2, "{:?}", file);
+        assert_eq!((images[0].page, images[0].width, images[0].height), (1, 1024, 768));
+        assert_eq!(images[0].encoding, "jpeg");
+        assert!(images[0].object_id.is_some());
+        assert_eq!(images[1].image_type, "smask");
+        //only poppler prints the resolution and size columns
+        if raw.lines().next().unwrap_or("").contains("x-ppi") {
+            assert!(images[1].x_ppi.is_some() && !images[1].size.is_empty(), "{:?}", file);
+        }
+    }
+}
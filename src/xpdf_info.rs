@@ -1,73 +1,51 @@
-use crate::{types::{self, XpdfInfoMap}, PdfErrorKind};
+use crate::{encoding, password::{self, PdfPassword}, types::{XpdfArgs, XpdfInfoMap}, PdfErrorKind};
 
-use std::process::Command;
 use std::path::Path;
 
-use super::{PdfError, XpdfTools, args_parser};
+use super::{PdfError, XpdfTools};
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PdfInfo {
     pub raw: String,
     pub info_map: XpdfInfoMap,
 }
 
+impl PdfInfo {
+    /// Parses stored pdfinfo output, the args are those the output was created with
+    pub fn parse(raw: &str, args: &[XpdfArgs]) -> Self {
+        PdfInfo {
+            raw: raw.to_string(),
+            info_map: parse_info_map(raw, args.contains(&XpdfArgs::Metadata)),
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub fn pdf_info(pdf_file: &Path, tools: &XpdfTools) -> Result<PdfInfo, PdfError> {
-    let mut cmd = tools.tools_folder.clone();
-    cmd.push("pdfinfo");
+    pdf_info_with_password(pdf_file, tools, None)
+}
 
-    let mut args = vec![];
-    if let Some(extra) = &tools.extra_args {
-        args.extend(args_parser(&extra, "pdfinfo"));
-    }
-    args.push(String::from(pdf_file.to_str().unwrap()));
+pub fn pdf_info_with_password(pdf_file: &Path, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<PdfInfo, PdfError> {
+    let mut args = tools.tool_args("pdfinfo", password)?;
+    args.push(tools.path_arg(pdf_file));
     
-    let output = Command::new(cmd)
-    .args(&args)
-    .output();
+    let output = tools.run_tool_cached("pdfinfo", pdf_file, &args);
 
     match output {
         Ok(o) => {
-            
-            let mut map = types::XpdfInfoMap::create();
-            let result = String::from_utf8_lossy(&o.stdout);
-
-            let line_re = regex::Regex::new(r"(?<label>[a-zA-Z ]+):(?<value>.+)").unwrap();
-            
-            for line in result.lines() {
-                match line_re.captures(line) {
-                    Some(caps) => {
-                        
-                        let label = caps.name("label").unwrap().as_str();
-                        
-                        if map.contains_key(label) {
-                            let value = caps.name("value").unwrap().as_str();
-                            map.entry(label.into()).and_modify(|e| *e = Some(value.trim().into()));
-                        } 
-                    },
-                    None => (),
-                };
-                
+            if let Some(e) = tools.overflow_error("pdfinfo", pdf_file, &args, &o).or_else(|| tools.limit_error("pdfinfo", pdf_file, &args, &o)) {
+                return Err(e);
             }
-           
-            if args.iter().any(|e| e == "-meta") {
-                
-                let meta_re = regex::Regex::new(r"(?s)<\?xpacket begin=.*<?xpacket end=.*>").unwrap();
-                if let Some(meta_match) = meta_re.find(result.as_ref()) {
-                    let normalize_white = meta_match.as_str().lines().filter_map(|l| {
-      
-                        if l.trim_end() != "" {
-                            Some(l)
-                        } else {
-                            None
-                        }
-                    }).collect::<Vec<_>>().join("\n");
-                    map.entry("Metadata".into()).and_modify(|e| *e = Some(normalize_white));
-                } 
-                
+            if !o.success() && password::is_password_error(&o.stderr) {
+                return Err(PdfError::password_required(pdf_file, &o.stderr).with_command(tools.command("pdfinfo", &args, Some(&o))));
             }
+
+            let result = encoding::decode_output(&o.stdout, &tools.output_encoding("pdfinfo"))?;
+            let map = parse_info_map(&result, args.iter().any(|e| e == "-meta"));
+
             let pdf_info = PdfInfo {
-                raw: result.to_string(),
+                raw: result,
                 info_map: map,
             };
 
@@ -76,13 +54,89 @@ pub fn pdf_info(pdf_file: &Path, tools: &XpdfTools) -> Result<PdfInfo, PdfError>
         Err(_e) => {
            
             Err(
-                PdfError { 
-                    //message: "pdf_info error".to_string(),
-                    message: format!("pdf_info error: {:?}", pdf_file),
-                    process_message: _e.to_string(),
-                    error_kind: PdfErrorKind::PdfInfoError
-                }
+                PdfError::new(PdfErrorKind::PdfInfoError, format!("pdf_info error: {:?}", pdf_file), _e.to_string())
+                    .with_command(tools.command("pdfinfo", &args, None))
+                    .with_source(_e)
             )
         }
     }
-}
\ No newline at end of file
+}
+
+//Fills the known labels of the info map from pdfinfo output
+//Labels must start a line: poppler prints extra indented blocks (e.g. below "PDF subtype:")
+//with labels like "Title" which must not overwrite the document info
+fn parse_info_map(result: &str, meta: bool) -> XpdfInfoMap {
+    let mut map = XpdfInfoMap::create();
+
+    let line_re = regex::Regex::new(r"^(?<label>[a-zA-Z][a-zA-Z ]*):(?<value>.+)").unwrap();
+
+    for line in result.lines() {
+        if let Some(caps) = line_re.captures(line) {
+            let label = caps.name("label").unwrap().as_str();
+
+            if map.contains_key(label) {
+                let value = caps.name("value").unwrap().as_str();
+                map.entry(label.into()).and_modify(|e| *e = Some(value.trim().into()));
+            }
+        }
+    }
+
+    if meta {
+        let meta_re = regex::Regex::new(r"(?s)<\?xpacket begin=.*<?xpacket end=.*>").unwrap();
+        if let Some(meta_match) = meta_re.find(result) {
+            let normalize_white = meta_match.as_str().lines()
+                .filter(|l| !l.trim_end().is_empty())
+                .collect::<Vec<_>>().join("\n");
+            map.entry("Metadata".into()).and_modify(|e| *e = Some(normalize_white));
+        }
+    }
+    map
+}
+
+#[test]
+fn test_parse_poppler_info() {
+    let raw = r#"Title:           Annual report
+Producer:        LibreOffice 7.3
+Custom Metadata: no
+Metadata Stream: yes
+Tagged:          no
+UserProperties:  no
+Suspects:        no
+Form:            none
+JavaScript:      no
+Pages:           2
+Encrypted:       no
+Page size:       595.304 x 841.89 pts (A4)
+Page rot:        0
+File size:       20611 bytes
+Optimized:       no
+PDF version:     1.7
+PDF subtype:     PDF/A-1b:2005
+    Title:         ISO 19005 - Electronic document file format for long-term preservation (PDF/A)
+    Abbreviation:  PDF/A-1b
+"#;
+
+    let map = parse_info_map(raw, false);
+    assert_eq!(map["Title"], Some("Annual report".into()));
+    assert_eq!(map["Pages"], Some("2".into()));
+    assert_eq!(map["Page size"], Some("595.304 x 841.89 pts (A4)".into()));
+    assert!(!map.contains_key("JavaScript"));
+}
+
+#[test]
+fn test_info_fixtures() {
+    for file in crate::fixtures::layouts("pdfinfo") {
+        let raw = std::fs::read_to_string(&file).unwrap();
+        let info = PdfInfo::parse(&raw, &[XpdfArgs::Metadata]);
+        let map = &info.info_map;
+
+        assert_eq!(map["Title"], Some("Quarterly Report".into()), "{:?}", file);
+        assert_eq!(map["Pages"], Some("2".into()), "{:?}", file);
+        assert_eq!(map["Page size"], Some("612 x 792 pts (letter)".into()), "{:?}", file);
+        assert!(map["File size"].as_ref().is_some_and(|size| size.ends_with(" bytes")), "{:?}", file);
+        assert_eq!(map["Encrypted"], Some("no".into()), "{:?}", file);
+        if raw.contains("<?xpacket") {
+            assert!(map["Metadata"].as_ref().unwrap().contains("<dc:title>Quarterly Report</dc:title>"));
+        }
+    }
+}
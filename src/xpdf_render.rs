@@ -0,0 +1,25 @@
+use std::path::{Path, PathBuf};
+
+use crate::{limits::OutputFiles, password::PdfPassword, version::ToolBackend, PdfError, PdfErrorKind, XpdfTools};
+
+/// Renders the pages into the folder, one file per page named after the pdf file
+/// xpdf's pdftopng is preferred, poppler's pdftoppm is asked for png with -png,
+/// xpdf's pdftoppm can only write ppm files
+/// Returns the image files written by the run, named like the pdf file with the page number
+pub fn pdf_render_with_password(pdf_file: &Path, out_dir: &Path, dpi: u32, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<Vec<PathBuf>, PdfError> {
+    let root = crate::output_root(pdf_file, out_dir);
+    let mut options = vec!["-r".to_string(), dpi.to_string()];
+
+    let tool = if tools.pdf_check_tool("pdftopng").is_ok() && tools.backend("pdftopng") == ToolBackend::Xpdf {
+        "pdftopng"
+    } else {
+        if tools.backend("pdftoppm") == ToolBackend::Poppler {
+            options.insert(0, "-png".into());
+        }
+        "pdftoppm"
+    };
+
+    let before = crate::files_with_root(&root);
+    tools.run_pdf_tool_to_files(tool, pdf_file, password, &options, &[tools.path_arg(&root)], OutputFiles::root(&root), PdfErrorKind::PdfRenderError)?;
+    Ok(crate::written_files(&root, &before))
+}
@@ -1,46 +1,88 @@
-use std::{path::Path, process::Command};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::PdfErrorKind;
+use crate::{encoding, limits::OutputFiles, password::{self, PdfPassword}, types::XpdfArgs, PdfErrorKind};
 
-use super::{PdfError, XpdfTools, args_parser};
+use super::{PdfError, XpdfTools};
+
+/// pdftotext output split into pages
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PdfText {
+    /// number of the first page, 1 unless the text was extracted with -f
+    pub first_page: usize,
+    pub pages: Vec<String>,
+}
+
+impl PdfText {
+    /// Parses stored pdftotext output, the args are those the output was created with
+    /// pdftotext ends every page with a form feed, with -nopgbrk the whole text is one page
+    pub fn parse(raw: &str, args: &[XpdfArgs]) -> Self {
+        let first_page = args.iter().find_map(|arg| match arg {
+            XpdfArgs::FirstPage(number) => Some(*number),
+            _ => None,
+        }).unwrap_or(1);
+
+        let mut pages: Vec<String> = raw.split('\x0c').map(|page| page.to_string()).collect();
+        if pages.len() > 1 && pages.last().is_some_and(|page| page.trim().is_empty()) {
+            pages.pop();
+        }
+        PdfText { first_page, pages }
+    }
+
+    /// The pages with their page numbers
+    pub fn numbered_pages(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.pages.iter().enumerate().map(move |(index, page)| (self.first_page + index, page.as_str()))
+    }
+}
 
 #[allow(dead_code)]
 pub fn pdf_to_text(pdf_file: &Path, tools: &XpdfTools) -> Result<String, PdfError> {
-    let binary_result = pdf_to_binary(pdf_file, tools);
+    pdf_to_text_with_password(pdf_file, tools, None)
+}
+
+pub fn pdf_to_text_with_password(pdf_file: &Path, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<String, PdfError> {
+    let binary_result = pdf_to_binary_with_password(pdf_file, tools, password);
     match binary_result {
-        Ok(output) => Ok(String::from_utf8_lossy(&output).into_owned()),
+        Ok(output) => encoding::decode_output(&output, &tools.output_encoding("pdftotext")),
         Err(e) => Err(e),
     }
 }
 
 #[allow(dead_code)]
 pub fn pdf_to_binary(pdf_file: &Path, tools: &XpdfTools) -> Result<Vec<u8>, PdfError> {
+    pdf_to_binary_with_password(pdf_file, tools, None)
+}
+
+pub fn pdf_to_binary_with_password(pdf_file: &Path, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<Vec<u8>, PdfError> {
+    pdf_to_binary_with_options(pdf_file, tools, password, &[])
+}
+
+//Like pdf_to_binary_with_password, the options are added after the extra args
+pub(crate) fn pdf_to_binary_with_options(pdf_file: &Path, tools: &XpdfTools, password: Option<&PdfPassword>, options: &[String]) -> Result<Vec<u8>, PdfError> {
     
-    let mut cmd = tools.tools_folder.clone();
-    cmd.push("pdftotext");
-    
-    let mut args = vec![];
-    if let Some(extra) = &tools.extra_args {
-        args.extend(args_parser(&extra, "pdftotext"));
-    }
-    args.push(String::from(pdf_file.to_str().unwrap()));
+    let mut args = tools.tool_args("pdftotext", password)?;
+    args.extend_from_slice(options);
+    args.push(tools.path_arg(pdf_file));
     args.push("-".into());
     
-    let output = Command::new(cmd)
-    .args(&args)
-    .output();
+    let output = tools.run_tool_cached("pdftotext", pdf_file, &args);
 
     match output {
         Ok(o) => {
             //let result = String::from_utf8_lossy(&o.stdout);
-            if o.stdout.is_empty() {
+            if let Some(e) = tools.overflow_error("pdftotext", pdf_file, &args, &o) {
+                Err(e)
+            } else if let Some(e) = tools.limit_error("pdftotext", pdf_file, &args, &o) {
+                Err(e)
+            } else if !o.success() && password::is_password_error(&o.stderr) {
+                Err(PdfError::password_required(pdf_file, &o.stderr).with_command(tools.command("pdftotext", &args, Some(&o))))
+            } else if o.stdout.is_empty() {
                 Err(
-                    PdfError { 
-                        //message: "pdf_to_text parse error".to_string(),
-                        message: format!("pdf_to_text parse error: {:?}", pdf_file),
-                        process_message: String::from_utf8_lossy(&o.stderr).to_string(),
-                        error_kind: PdfErrorKind::PdfToTextErrorNoOutput,
-                    })
+                    PdfError::new(PdfErrorKind::PdfToTextErrorNoOutput, format!("pdf_to_text parse error: {:?}", pdf_file), String::from_utf8_lossy(&o.stderr).to_string())
+                        .with_command(tools.command("pdftotext", &args, Some(&o))))
             } else {
                 Ok(o.stdout.to_owned())
             }
@@ -48,13 +90,62 @@ pub fn pdf_to_binary(pdf_file: &Path, tools: &XpdfTools) -> Result<Vec<u8>, PdfE
         Err(_e) => {
            
             Err(
-                PdfError { 
-                    //message: "pdf_to_text parse error".to_string(),
-                    message: format!("pdf_to_text parse error: {:?}", pdf_file),
-                    process_message: _e.to_string(),
-                    error_kind: PdfErrorKind::PdfToTextError
-                }
+                PdfError::new(PdfErrorKind::PdfToTextError, format!("pdf_to_text parse error: {:?}", pdf_file), _e.to_string())
+                    .with_command(tools.command("pdftotext", &args, None))
+                    .with_source(_e)
             )
         }
     }
-}
\ No newline at end of file
+}
+
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+//A file removed on drop unless it was renamed
+struct TempFile(PathBuf);
+
+impl TempFile {
+    //Next to the target, so the rename stays on the same file system
+    fn next_to(target: &Path) -> Self {
+        let name = format!(".{}.{}-{}.tmp", target.file_name().unwrap_or_default().to_string_lossy(), std::process::id(), TEMP_FILES.fetch_add(1, Ordering::Relaxed));
+        TempFile(target.with_file_name(name))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Lets pdftotext write the text file itself, into a temporary file renamed to out_path on success
+/// A failed run leaves an existing out_path untouched
+pub fn pdf_text_to_file_with_password(pdf_file: &Path, out_path: &Path, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<(), PdfError> {
+    let tmp = TempFile::next_to(out_path);
+    tools.run_pdf_tool_to_files("pdftotext", pdf_file, password, &[], &[tools.path_arg(&tmp.0)], OutputFiles::root(&tmp.0), PdfErrorKind::PdfToTextError)?;
+    fs::rename(&tmp.0, out_path).map_err(|e| write_error(out_path, e))
+}
+
+/// Streams the text into the writer as pdftotext writes it to stdout, the text is never held in full
+/// A failing run can leave part of the text in the writer
+pub fn pdf_text_to_writer_with_password(pdf_file: &Path, out: &mut impl Write, tools: &XpdfTools, password: Option<&PdfPassword>) -> Result<u64, PdfError> {
+    tools.run_pdf_tool_streamed("pdftotext", pdf_file, password, &["-".into()], out, PdfErrorKind::PdfToTextError)
+}
+
+fn write_error(path: &Path, e: io::Error) -> PdfError {
+    PdfError::new(PdfErrorKind::PdfToTextError, format!("Could not write the text to {:?}", path), e.to_string()).with_source(e)
+}
+
+#[test]
+fn test_text_fixtures() {
+    for file in crate::fixtures::layouts("pdftotext") {
+        let raw = std::fs::read_to_string(&file).unwrap();
+        let text = PdfText::parse(&raw, &[]);
+
+        assert_eq!(text.pages.len(), 2, "{:?}", file);
+        assert!(text.pages[0].starts_with("Quarterly Report"));
+        assert!(text.pages[1].contains("Outlook remains stable."));
+    }
+
+    let text = PdfText::parse("third\x0cfourth\x0c", &[XpdfArgs::FirstPage(3)]);
+    assert_eq!(text.numbered_pages().collect::<Vec<_>>(), vec![(3, "third"), (4, "fourth")]);
+}